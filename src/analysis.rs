@@ -0,0 +1,620 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+//! Reason about the relationship between two patterns, like whether they can
+//! match the same input.
+//!
+//! The analysis works component by component on the part lists of the
+//! patterns. Fixed text, segment wildcards (`:name`), full wildcards (`*`) and
+//! their modifiers only describe regular languages, so questions about them
+//! are answered exactly. Custom regexp groups like `:id(\d+)` can not be
+//! reasoned about. They are treated as if they matched anything, which still
+//! answers some questions (`/users/:id(\d+)` never matches the same input as
+//! `/posts/:id`), and gives [Answer::Unknown] for all others.
+//!
+//! Components are compared as strings, like the matcher sees them. Whether a
+//! string is a valid value for the component of a URL is not taken into
+//! account.
+//!
+//! # Examples
+//!
+//! ```
+//! use urlpattern::UrlPattern;
+//! use urlpattern::analysis::Answer;
+//!
+//!# fn main() {
+//! let parse = |pattern| {
+//!   <UrlPattern>::parse_str(pattern, None, Default::default()).unwrap()
+//! };
+//! let users = parse("https://example.com/users/:id");
+//! let all = parse("https://example.com/*");
+//! let new = parse("https://example.com/users/new");
+//! let numeric = parse("https://example.com/users/:id(\\d+)");
+//!
+//! assert_eq!(users.is_subset_of(&all), Answer::Yes);
+//! assert_eq!(all.is_subset_of(&users), Answer::No);
+//! assert_eq!(users.intersects(&new), Answer::Yes);
+//! assert_eq!(users.intersects(&numeric), Answer::Unknown);
+//!# }
+//! ```
+
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+use crate::ComponentKind;
+use crate::UrlPattern;
+use crate::canonicalize_and_process::is_special_scheme;
+use crate::canonicalize_and_process::special_scheme_default_port;
+use crate::component::Component;
+use crate::matcher::InnerMatcher;
+use crate::parser::MatchMode;
+use crate::parser::Options;
+use crate::parser::Part;
+use crate::parser::PartModifier;
+use crate::parser::PartType;
+use crate::regexp::RegExp;
+
+/// The number of states that are explored to decide whether one component is
+/// a subset of another, before giving up with [Answer::Unknown].
+const MAX_STATES: usize = 100_000;
+
+/// The answer to a question about patterns, which can be undecidable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+  Yes,
+  No,
+  /// The question can not be decided, for example because a pattern contains
+  /// a custom regexp group.
+  Unknown,
+}
+
+impl Answer {
+  /// Returns whether the answer is definitely yes.
+  pub fn is_yes(self) -> bool {
+    self == Answer::Yes
+  }
+
+  /// Returns whether the answer is definitely no.
+  pub fn is_no(self) -> bool {
+    self == Answer::No
+  }
+
+  /// Combine the answers for two independent questions that must both be
+  /// answered with yes.
+  pub(crate) fn and(self, other: Answer) -> Answer {
+    match (self, other) {
+      (Answer::No, _) | (_, Answer::No) => Answer::No,
+      (Answer::Unknown, _) | (_, Answer::Unknown) => Answer::Unknown,
+      (Answer::Yes, Answer::Yes) => Answer::Yes,
+    }
+  }
+}
+
+impl From<bool> for Answer {
+  fn from(value: bool) -> Self {
+    if value { Answer::Yes } else { Answer::No }
+  }
+}
+
+/// Returns whether there is a string that both components match.
+pub(crate) fn intersects<R: RegExp>(
+  left: &Component<R>,
+  right: &Component<R>,
+) -> Answer {
+  part_lists_intersect(
+    (&left.part_list, &left.options),
+    (&right.part_list, &right.options),
+  )
+}
+
+fn part_lists_intersect(
+  left: (&[Part], &Options),
+  right: (&[Part], &Options),
+) -> Answer {
+  let Some((left, right, alphabet)) = automata(left, right) else {
+    return Answer::Unknown;
+  };
+  // Note: regexp groups match more inputs in the automata than they really
+  // do, so only the absence of a common input is certain.
+  let exact = if left.exact && right.exact {
+    Answer::Yes
+  } else {
+    Answer::Unknown
+  };
+  let mut seen = HashSet::new();
+  let mut queue = VecDeque::from([(left.start, right.start)]);
+  while let Some((a, b)) = queue.pop_front() {
+    if !seen.insert((a, b)) {
+      continue;
+    }
+    if a == left.accept && b == right.accept {
+      return exact;
+    }
+    let (a_state, b_state) = (&left.states[a], &right.states[b]);
+    queue.extend(a_state.epsilon.iter().map(|&a| (a, b)));
+    queue.extend(b_state.epsilon.iter().map(|&b| (a, b)));
+    for (a_set, a) in &a_state.transitions {
+      for (b_set, b) in &b_state.transitions {
+        if alphabet
+          .iter()
+          .any(|&symbol| a_set.matches(symbol) && b_set.matches(symbol))
+        {
+          queue.push_back((*a, *b));
+        }
+      }
+    }
+  }
+  Answer::No
+}
+
+/// Returns whether every string the left component matches is also matched
+/// by the right component.
+pub(crate) fn is_subset<R: RegExp>(
+  left: &Component<R>,
+  right: &Component<R>,
+) -> Answer {
+  let Some((left, right, alphabet)) = automata(
+    (&left.part_list, &left.options),
+    (&right.part_list, &right.options),
+  ) else {
+    return Answer::Unknown;
+  };
+  // Note: regexp groups match more inputs in the automata than they really
+  // do. This only gives a certain answer if the left automaton has them.
+  if !right.exact {
+    return Answer::Unknown;
+  }
+  // The right automaton is determinized on the fly: every left state is
+  // paired with the set of right states reachable with the same input. The
+  // left component is not a subset if a left state that accepts is paired
+  // with a set that does not.
+  let mut seen = HashSet::new();
+  let mut queue = VecDeque::from([(left.start, right.closure([right.start]))]);
+  while let Some((a, b)) = queue.pop_front() {
+    if seen.contains(&(a, b.clone())) {
+      continue;
+    }
+    if seen.len() >= MAX_STATES {
+      return Answer::Unknown;
+    }
+    // Note: every state of an automaton can reach its accepting state, so
+    // once no right state is left, the left component can still match an
+    // input that the right one can not.
+    if (a == left.accept && !b.contains(&right.accept)) || b.is_empty() {
+      return if left.exact {
+        Answer::No
+      } else {
+        Answer::Unknown
+      };
+    }
+    let a_state = &left.states[a];
+    queue.extend(a_state.epsilon.iter().map(|&a| (a, b.clone())));
+    for (set, a) in &a_state.transitions {
+      for &symbol in alphabet.iter().filter(|&&symbol| set.matches(symbol)) {
+        queue.push_back((*a, right.step(&b, symbol)));
+      }
+    }
+    seen.insert((a, b));
+  }
+  Answer::Yes
+}
+
+/// The problems found in an ordered list of patterns by [check_routes].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RouteReport {
+  /// Patterns that are never reached, as the index of the pattern and the
+  /// index of an earlier pattern that matches every input it matches.
+  pub shadowed: Vec<(usize, usize)>,
+  /// Pairs of patterns that match some of the same inputs, where neither
+  /// matches every input of the other, so their order decides which one
+  /// matches. The earlier pattern comes first.
+  pub overlaps: Vec<(usize, usize)>,
+  /// Patterns that can never match any URL.
+  pub unreachable: Vec<Unreachable>,
+}
+
+impl RouteReport {
+  /// Returns whether no problems were found.
+  pub fn is_empty(&self) -> bool {
+    self.shadowed.is_empty()
+      && self.overlaps.is_empty()
+      && self.unreachable.is_empty()
+  }
+}
+
+/// A pattern that can never match any URL, because of one of its components.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unreachable {
+  /// The index of the pattern.
+  pub index: usize,
+  /// The component that can never match.
+  pub component: ComponentKind,
+  pub reason: UnreachableReason,
+}
+
+/// Why a component of a pattern can never match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnreachableReason {
+  /// The pathname does not start with a `/`, but the pathname of a URL with a
+  /// special scheme always does.
+  NoLeadingSlash,
+  /// The hostname is empty, but a URL with a special scheme other than
+  /// `file` always has a host.
+  EmptyHostname,
+  /// The port is the default port of the scheme, which is never part of a
+  /// URL.
+  DefaultPort,
+}
+
+impl std::fmt::Display for UnreachableReason {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      UnreachableReason::NoLeadingSlash => {
+        "the pathname of a URL with a special scheme starts with a '/'"
+      }
+      UnreachableReason::EmptyHostname => {
+        "a URL with a special scheme other than 'file' has a host"
+      }
+      UnreachableReason::DefaultPort => {
+        "the default port of the scheme is removed from a URL"
+      }
+    })
+  }
+}
+
+/// Check an ordered list of patterns, of which the first one that matches an
+/// input wins, like the routes of a proxy configuration.
+///
+/// A pattern is shadowed if an earlier pattern matches every input it
+/// matches, and two patterns overlap if they match some of the same inputs,
+/// without either of them shadowing the other. Only problems that are certain
+/// are reported: pairs of patterns whose relationship is [Answer::Unknown]
+/// are skipped. Unreachable and shadowed patterns are not compared with
+/// later patterns, as they never match anything.
+///
+/// # Examples
+///
+/// ```
+/// use urlpattern::UrlPattern;
+/// use urlpattern::analysis::check_routes;
+///
+///# fn main() {
+/// let patterns: Vec<UrlPattern> = [
+///   "https://example.com/users/:id",
+///   "https://example.com/users/new",
+///   "https://example.com/:section/about",
+///   "https://example.com/*",
+/// ]
+/// .into_iter()
+/// .map(|pattern| UrlPattern::parse_str(pattern, None, Default::default()).unwrap())
+/// .collect();
+///
+/// let report = check_routes(&patterns);
+/// assert_eq!(report.shadowed, [(1, 0)]);
+/// assert_eq!(report.overlaps, [(0, 2)]);
+/// assert!(report.unreachable.is_empty());
+///# }
+/// ```
+pub fn check_routes<R: RegExp>(patterns: &[UrlPattern<R>]) -> RouteReport {
+  let mut report = RouteReport::default();
+  let mut reachable: Vec<usize> = vec![];
+  for (index, pattern) in patterns.iter().enumerate() {
+    let unreachable = unreachable_components(pattern);
+    if !unreachable.is_empty() {
+      report.unreachable.extend(unreachable.into_iter().map(
+        |(component, reason)| Unreachable {
+          index,
+          component,
+          reason,
+        },
+      ));
+      continue;
+    }
+    let shadowed_by = reachable
+      .iter()
+      .find(|&&earlier| pattern.is_subset_of(&patterns[earlier]).is_yes());
+    if let Some(&earlier) = shadowed_by {
+      report.shadowed.push((index, earlier));
+      continue;
+    }
+    for &earlier in &reachable {
+      let earlier_pattern = &patterns[earlier];
+      if pattern.intersects(earlier_pattern).is_yes()
+        && pattern.is_subset_of(earlier_pattern).is_no()
+        && earlier_pattern.is_subset_of(pattern).is_no()
+      {
+        report.overlaps.push((earlier, index));
+      }
+    }
+    reachable.push(index);
+  }
+  report
+}
+
+/// The components of a pattern that can never match the corresponding
+/// component of a URL. Only patterns with a fixed special scheme are checked.
+fn unreachable_components<R: RegExp>(
+  pattern: &UrlPattern<R>,
+) -> Vec<(ComponentKind, UnreachableReason)> {
+  let mut unreachable = vec![];
+  let protocol = match &pattern.protocol.matcher.inner {
+    InnerMatcher::Literal { literal } if is_special_scheme(literal) => literal,
+    _ => return unreachable,
+  };
+  let slash = [
+    Part::new(PartType::FixedText, "/".to_owned(), PartModifier::None),
+    Part::new(PartType::FullWildcard, String::new(), PartModifier::None),
+  ];
+  let pathname = (&pattern.pathname.part_list[..], &pattern.pathname.options);
+  if part_lists_intersect(pathname, (&slash, &pattern.pathname.options)).is_no()
+  {
+    unreachable
+      .push((ComponentKind::Pathname, UnreachableReason::NoLeadingSlash));
+  }
+  if let InnerMatcher::Literal { literal } = &pattern.hostname.matcher.inner {
+    if literal.is_empty() && protocol != "file" {
+      unreachable
+        .push((ComponentKind::Hostname, UnreachableReason::EmptyHostname));
+    }
+  }
+  if let InnerMatcher::Literal { literal } = &pattern.port.matcher.inner {
+    if special_scheme_default_port(protocol) == Some(literal.as_str()) {
+      unreachable.push((ComponentKind::Port, UnreachableReason::DefaultPort));
+    }
+  }
+  unreachable
+}
+
+/// A char of an input. Only the chars that occur in the fixed text of the
+/// compared components and the delimiter are distinguished, all other chars
+/// behave the same and are represented by `None`.
+type Symbol = Option<char>;
+
+/// The chars a transition of an automaton consumes.
+#[derive(Debug, Clone, Copy)]
+enum CharSet {
+  Char(char),
+  /// Any char except the given one, if it is set.
+  AllExcept(Option<char>),
+}
+
+impl CharSet {
+  fn matches(self, symbol: Symbol) -> bool {
+    match self {
+      CharSet::Char(char) => symbol == Some(char),
+      CharSet::AllExcept(None) => true,
+      CharSet::AllExcept(except) => symbol != except,
+    }
+  }
+}
+
+#[derive(Debug, Default)]
+struct State {
+  epsilon: Vec<usize>,
+  transitions: Vec<(CharSet, usize)>,
+}
+
+/// A nondeterministic finite automaton that accepts the strings a component
+/// matches.
+#[derive(Debug, Default)]
+struct Automaton {
+  states: Vec<State>,
+  start: usize,
+  accept: usize,
+  /// Whether the automaton accepts exactly the strings the component matches.
+  /// If not, it accepts more.
+  exact: bool,
+}
+
+impl Automaton {
+  /// Build the automaton for a part list. A regexp group is handled like a
+  /// full wildcard, so that the automaton is not exact.
+  fn from_parts(part_list: &[Part], options: &Options) -> Self {
+    let delimiter = options.delimiter_code_point;
+    let fold_case = options.ignore_case;
+    let mut automaton = Automaton {
+      exact: true,
+      ..Default::default()
+    };
+    let start = automaton.add_state();
+    let mut end = start;
+    for part in part_list {
+      let text = |text: &str| {
+        if fold_case {
+          text.to_ascii_lowercase()
+        } else {
+          text.to_owned()
+        }
+      };
+      if part.kind == PartType::Regexp {
+        automaton.exact = false;
+      }
+      let (part_start, part_end) = match part.kind {
+        PartType::FixedText => automaton.literal(&text(&part.value)),
+        PartType::SegmentWildcard
+        | PartType::FullWildcard
+        | PartType::Regexp => {
+          let (prefix_start, prefix_end) =
+            automaton.literal(&text(&part.prefix));
+          let wildcard = automaton.add_state();
+          if part.kind == PartType::SegmentWildcard {
+            // One or more chars that are not the delimiter.
+            let set = CharSet::AllExcept(delimiter);
+            automaton.states[prefix_end]
+              .transitions
+              .push((set, wildcard));
+            automaton.states[wildcard].transitions.push((set, wildcard));
+          } else {
+            // Zero or more chars.
+            let set = CharSet::AllExcept(None);
+            automaton.states[prefix_end].epsilon.push(wildcard);
+            automaton.states[wildcard].transitions.push((set, wildcard));
+          }
+          let (suffix_start, suffix_end) =
+            automaton.literal(&text(&part.suffix));
+          automaton.states[wildcard].epsilon.push(suffix_start);
+          (prefix_start, suffix_end)
+        }
+      };
+      let (part_start, part_end) =
+        automaton.repeat(part_start, part_end, part.modifier);
+      automaton.states[end].epsilon.push(part_start);
+      end = part_end;
+    }
+    if options.match_mode == MatchMode::Prefix {
+      end = automaton.remainder(part_list, end, delimiter);
+    }
+    automaton.start = start;
+    automaton.accept = end;
+    automaton
+  }
+
+  /// Add the states that match the remainder of an input in
+  /// [MatchMode::Prefix] after `end`, which is either empty or starts with
+  /// the delimiter. If the part list ends with the delimiter itself, the
+  /// remainder can be anything.
+  fn remainder(
+    &mut self,
+    part_list: &[Part],
+    end: usize,
+    delimiter: Option<char>,
+  ) -> usize {
+    let ends_with_delimiter = part_list.last().is_some_and(|part| {
+      part.kind == PartType::FixedText
+        && part.modifier == PartModifier::None
+        && delimiter.is_some_and(|delimiter| part.value.ends_with(delimiter))
+    });
+    let any = self.add_state();
+    self.states[any]
+      .transitions
+      .push((CharSet::AllExcept(None), any));
+    match delimiter {
+      Some(delimiter) if !ends_with_delimiter => {
+        let accept = self.add_state();
+        self.states[end].epsilon.push(accept);
+        self.states[end]
+          .transitions
+          .push((CharSet::Char(delimiter), any));
+        self.states[any].epsilon.push(accept);
+        accept
+      }
+      _ => {
+        self.states[end].epsilon.push(any);
+        any
+      }
+    }
+  }
+
+  fn add_state(&mut self) -> usize {
+    self.states.push(State::default());
+    self.states.len() - 1
+  }
+
+  /// Add a chain of states that matches the given text.
+  fn literal(&mut self, text: &str) -> (usize, usize) {
+    let start = self.add_state();
+    let mut end = start;
+    for char in text.chars() {
+      let next = self.add_state();
+      self.states[end]
+        .transitions
+        .push((CharSet::Char(char), next));
+      end = next;
+    }
+    (start, end)
+  }
+
+  /// Wrap the states from `start` to `end` so that they are matched as often
+  /// as the modifier allows.
+  fn repeat(
+    &mut self,
+    start: usize,
+    end: usize,
+    modifier: PartModifier,
+  ) -> (usize, usize) {
+    if modifier == PartModifier::None {
+      return (start, end);
+    }
+    let (outer_start, outer_end) = (self.add_state(), self.add_state());
+    self.states[outer_start].epsilon.push(start);
+    self.states[end].epsilon.push(outer_end);
+    if matches!(modifier, PartModifier::Optional | PartModifier::ZeroOrMore) {
+      self.states[outer_start].epsilon.push(outer_end);
+    }
+    if matches!(modifier, PartModifier::ZeroOrMore | PartModifier::OneOrMore) {
+      self.states[end].epsilon.push(start);
+    }
+    (outer_start, outer_end)
+  }
+
+  /// The states reachable from the given states without consuming a char.
+  fn closure(
+    &self,
+    states: impl IntoIterator<Item = usize>,
+  ) -> BTreeSet<usize> {
+    let mut closure = BTreeSet::new();
+    let mut stack: Vec<usize> = states.into_iter().collect();
+    while let Some(state) = stack.pop() {
+      if closure.insert(state) {
+        stack.extend(&self.states[state].epsilon);
+      }
+    }
+    closure
+  }
+
+  /// The states reachable from the given states by consuming `symbol`.
+  fn step(&self, states: &BTreeSet<usize>, symbol: Symbol) -> BTreeSet<usize> {
+    self.closure(states.iter().flat_map(|&state| {
+      self.states[state]
+        .transitions
+        .iter()
+        .filter(move |(set, _)| set.matches(symbol))
+        .map(|(_, next)| *next)
+    }))
+  }
+}
+
+/// Build the automata of the part lists of two components of the same kind,
+/// and the alphabet of chars they distinguish between. Returns `None` if the
+/// components are matched with different case sensitivity.
+fn automata(
+  (left, left_options): (&[Part], &Options),
+  (right, right_options): (&[Part], &Options),
+) -> Option<(Automaton, Automaton, Vec<Symbol>)> {
+  let fold_case = left_options.ignore_case;
+  if right_options.ignore_case != fold_case {
+    return None;
+  }
+  let texts = || {
+    left.iter().chain(right).flat_map(|part| {
+      let value = match part.kind {
+        PartType::FixedText => part.value.as_str(),
+        _ => "",
+      };
+      [value, &part.prefix, &part.suffix]
+    })
+  };
+  // Note: the regexp engine folds the case of unicode chars in ways that are
+  // not modelled here.
+  if fold_case && !texts().all(|text| text.is_ascii()) {
+    return None;
+  }
+  let delimiter = left_options.delimiter_code_point;
+  let mut alphabet: BTreeSet<Symbol> = texts()
+    .flat_map(str::chars)
+    .map(|char| {
+      if fold_case {
+        Some(char.to_ascii_lowercase())
+      } else {
+        Some(char)
+      }
+    })
+    .collect();
+  alphabet.insert(delimiter);
+  alphabet.insert(None);
+  Some((
+    Automaton::from_parts(left, left_options),
+    Automaton::from_parts(right, right_options),
+    alphabet.into_iter().collect(),
+  ))
+}
@@ -0,0 +1,477 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+//! Build patterns from typed parts instead of pattern strings.
+//!
+//! Parts are turned into the part list of a component directly, without
+//! tokenizing a pattern string. The text of [fixed] parts, prefixes and
+//! suffixes is always matched literally, so it never has to be escaped. The
+//! part list is normalized like a parsed one, so a built pattern has the same
+//! parts as the equivalent parsed pattern.
+//!
+//! # Examples
+//!
+//! ```
+//! use urlpattern::UrlPattern;
+//! use urlpattern::builder::UrlPatternBuilder;
+//! use urlpattern::builder::fixed;
+//! use urlpattern::builder::segment;
+//! use urlpattern::builder::wildcard;
+//!
+//!# fn main() {
+//! let pattern: UrlPattern = UrlPatternBuilder::new()
+//!   .pathname([
+//!     fixed("/files (1)"),
+//!     segment("id").regex("\\d+").prefix("/"),
+//!     wildcard().prefix("/").optional(),
+//!   ])
+//!   .build()
+//!   .unwrap();
+//! assert_eq!(pattern.pathname(), "/files%20\\(1\\)/:id(\\d+)/*?");
+//!
+//! let url = "https://example.com/files%20(1)/42/a/b";
+//! let result = pattern.exec_str(url, None).unwrap().unwrap();
+//! assert_eq!(result.pathname.groups["id"].as_deref(), Some("42"));
+//! assert_eq!(result.pathname.groups["0"].as_deref(), Some("a/b"));
+//!# }
+//! ```
+
+use crate::ComponentKind;
+use crate::Error;
+use crate::UrlPattern;
+use crate::UrlPatternOptions;
+use crate::canonicalize_and_process::is_special_scheme;
+use crate::canonicalize_and_process::special_scheme_default_port;
+use crate::component::Component;
+use crate::error::BuildError;
+use crate::error::TokenizerError;
+use crate::parser::FULL_WILDCARD_REGEXP_VALUE;
+use crate::parser::Part;
+use crate::parser::PartModifier;
+use crate::parser::PartType;
+use crate::regexp::RegExp;
+use crate::tokenizer::TokenType;
+use crate::tokenizer::TokenizePolicy;
+use crate::tokenizer::is_valid_name_codepoint;
+use crate::tokenizer::tokenize;
+
+/// A part of a component, created with [fixed], [segment] or [wildcard].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartBuilder {
+  kind: PartType,
+  value: String,
+  name: Option<String>,
+  prefix: String,
+  suffix: String,
+  modifier: PartModifier,
+}
+
+/// Fixed text that is matched literally, like `/users` in `/users/:id`.
+pub fn fixed(text: impl Into<String>) -> PartBuilder {
+  PartBuilder::new(PartType::FixedText, text.into(), None)
+}
+
+/// A named group that matches a single segment of the component, like `:id`
+/// in `/users/:id`. Use [PartBuilder::regex] to match a custom regular
+/// expression instead.
+pub fn segment(name: impl Into<String>) -> PartBuilder {
+  PartBuilder::new(PartType::SegmentWildcard, String::new(), Some(name.into()))
+}
+
+/// An unnamed group that matches anything, like `*` in `/files/*`. Like in a
+/// pattern string, unnamed groups are named by their index.
+pub fn wildcard() -> PartBuilder {
+  PartBuilder::new(PartType::FullWildcard, String::new(), None)
+}
+
+impl PartBuilder {
+  fn new(kind: PartType, value: String, name: Option<String>) -> Self {
+    PartBuilder {
+      kind,
+      value,
+      name,
+      prefix: String::new(),
+      suffix: String::new(),
+      modifier: PartModifier::None,
+    }
+  }
+
+  /// Match a regular expression with the group, like `(\d+)` in `:id(\d+)`.
+  /// Like in a pattern string, the regular expression may not contain
+  /// capturing groups.
+  pub fn regex(mut self, regex: impl Into<String>) -> Self {
+    if self.kind != PartType::FixedText {
+      self.kind = PartType::Regexp;
+      self.value = regex.into();
+    }
+    self
+  }
+
+  /// Fixed text that is matched before the group, and is only matched if the
+  /// group is, like `/` in `{/:id}?`. For fixed text, this is prepended to the
+  /// text.
+  pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+    self.prefix = prefix.into();
+    self
+  }
+
+  /// Fixed text that is matched after the group, and is only matched if the
+  /// group is, like `.` in `{:subdomain.}?`. For fixed text, this is appended
+  /// to the text.
+  pub fn suffix(mut self, suffix: impl Into<String>) -> Self {
+    self.suffix = suffix.into();
+    self
+  }
+
+  /// Match the part zero or one times, like the `?` modifier.
+  pub fn optional(self) -> Self {
+    self.modifier(PartModifier::Optional)
+  }
+
+  /// Match the part any number of times, like the `*` modifier.
+  pub fn zero_or_more(self) -> Self {
+    self.modifier(PartModifier::ZeroOrMore)
+  }
+
+  /// Match the part at least once, like the `+` modifier.
+  pub fn one_or_more(self) -> Self {
+    self.modifier(PartModifier::OneOrMore)
+  }
+
+  fn modifier(mut self, modifier: PartModifier) -> Self {
+    self.modifier = modifier;
+    self
+  }
+}
+
+/// Build a [UrlPattern] from the parts of its components. Components that are
+/// not set match anything, like a missing component of a
+/// [crate::UrlPatternInit].
+#[derive(Debug, Clone, Default)]
+pub struct UrlPatternBuilder {
+  protocol: Option<Vec<PartBuilder>>,
+  username: Option<Vec<PartBuilder>>,
+  password: Option<Vec<PartBuilder>>,
+  hostname: Option<Vec<PartBuilder>>,
+  port: Option<Vec<PartBuilder>>,
+  pathname: Option<Vec<PartBuilder>>,
+  search: Option<Vec<PartBuilder>>,
+  hash: Option<Vec<PartBuilder>>,
+  options: UrlPatternOptions,
+}
+
+impl UrlPatternBuilder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Set the parts of the component of the given kind.
+  pub fn component(
+    mut self,
+    kind: ComponentKind,
+    parts: impl IntoIterator<Item = PartBuilder>,
+  ) -> Self {
+    *self.parts_mut(kind) = Some(parts.into_iter().collect());
+    self
+  }
+
+  pub fn protocol(self, parts: impl IntoIterator<Item = PartBuilder>) -> Self {
+    self.component(ComponentKind::Protocol, parts)
+  }
+
+  pub fn username(self, parts: impl IntoIterator<Item = PartBuilder>) -> Self {
+    self.component(ComponentKind::Username, parts)
+  }
+
+  pub fn password(self, parts: impl IntoIterator<Item = PartBuilder>) -> Self {
+    self.component(ComponentKind::Password, parts)
+  }
+
+  pub fn hostname(self, parts: impl IntoIterator<Item = PartBuilder>) -> Self {
+    self.component(ComponentKind::Hostname, parts)
+  }
+
+  pub fn port(self, parts: impl IntoIterator<Item = PartBuilder>) -> Self {
+    self.component(ComponentKind::Port, parts)
+  }
+
+  pub fn pathname(self, parts: impl IntoIterator<Item = PartBuilder>) -> Self {
+    self.component(ComponentKind::Pathname, parts)
+  }
+
+  pub fn search(self, parts: impl IntoIterator<Item = PartBuilder>) -> Self {
+    self.component(ComponentKind::Search, parts)
+  }
+
+  pub fn hash(self, parts: impl IntoIterator<Item = PartBuilder>) -> Self {
+    self.component(ComponentKind::Hash, parts)
+  }
+
+  pub fn options(mut self, options: UrlPatternOptions) -> Self {
+    self.options = options;
+    self
+  }
+
+  fn parts_mut(
+    &mut self,
+    kind: ComponentKind,
+  ) -> &mut Option<Vec<PartBuilder>> {
+    match kind {
+      ComponentKind::Protocol => &mut self.protocol,
+      ComponentKind::Username => &mut self.username,
+      ComponentKind::Password => &mut self.password,
+      ComponentKind::Hostname => &mut self.hostname,
+      ComponentKind::Port => &mut self.port,
+      ComponentKind::Pathname => &mut self.pathname,
+      ComponentKind::Search => &mut self.search,
+      ComponentKind::Hash => &mut self.hash,
+    }
+  }
+
+  /// Compile the parts into a [UrlPattern].
+  pub fn build<R: RegExp>(mut self) -> Result<UrlPattern<R>, Error> {
+    // If the protocol is a special scheme and the port is its default port,
+    // the port is removed, like when processing an init.
+    let default_port = match self.protocol.as_deref() {
+      Some([part]) if is_fixed(part) => {
+        let protocol = part.value.to_ascii_lowercase();
+        is_special_scheme(&protocol)
+          .then(|| special_scheme_default_port(&protocol))
+          .flatten()
+      }
+      _ => None,
+    };
+    if let Some([part]) = self.port.as_deref() {
+      if is_fixed(part) && Some(part.value.as_str()) == default_port {
+        self.port = Some(vec![]);
+      }
+    }
+
+    let options = &self.options;
+    let compile =
+      |kind, parts: &Option<Vec<PartBuilder>>, opaque_pathname| match parts {
+        Some(parts) => compile_parts(kind, parts, options, opaque_pathname),
+        None => {
+          let (encoding_callback, compile_options) =
+            crate::component_options(kind, "*", options, opaque_pathname);
+          Component::compile(kind, None, encoding_callback, compile_options)
+        }
+      };
+
+    let protocol = compile(ComponentKind::Protocol, &self.protocol, false)?;
+    // Like for an init, the pathname is an opaque path unless the protocol
+    // is empty or a special scheme, or the pathname starts with a `/`.
+    let protocol_is_empty = self.protocol.as_ref().is_some_and(Vec::is_empty);
+    let has_leading_slash = self.pathname.as_deref().is_some_and(|parts| {
+      parts.first().is_some_and(|part| {
+        part.prefix.starts_with('/')
+          || (part.prefix.is_empty() && part.value.starts_with('/'))
+      })
+    });
+    let opaque_pathname = !(protocol_is_empty
+      || protocol.protocol_component_matches_special_scheme()
+      || has_leading_slash);
+
+    Ok(UrlPattern {
+      protocol,
+      username: compile(ComponentKind::Username, &self.username, false)?,
+      password: compile(ComponentKind::Password, &self.password, false)?,
+      hostname: compile(ComponentKind::Hostname, &self.hostname, false)?,
+      port: compile(ComponentKind::Port, &self.port, false)?,
+      pathname: compile(
+        ComponentKind::Pathname,
+        &self.pathname,
+        opaque_pathname,
+      )?,
+      search: compile(ComponentKind::Search, &self.search, false)?,
+      hash: compile(ComponentKind::Hash, &self.hash, false)?,
+    })
+  }
+}
+
+/// Compile a single component from its parts. The pathname is compiled as a
+/// hierarchical path, and not as an opaque path.
+pub fn build_component<R: RegExp>(
+  kind: ComponentKind,
+  parts: &[PartBuilder],
+  options: &UrlPatternOptions,
+) -> Result<Component<R>, Error> {
+  compile_parts(kind, parts, options, false)
+}
+
+fn compile_parts<R: RegExp>(
+  kind: ComponentKind,
+  parts: &[PartBuilder],
+  options: &UrlPatternOptions,
+  opaque_pathname: bool,
+) -> Result<Component<R>, Error> {
+  // The encoding of the hostname depends on whether it is an IPv6 address.
+  let input = match parts.first() {
+    Some(part) if part.kind == PartType::FixedText => part.value.as_str(),
+    _ => "",
+  };
+  let (encoding_callback, compile_options) =
+    crate::component_options(kind, input, options, opaque_pathname);
+  let segment_wildcard_regexp =
+    compile_options.generate_segment_wildcard_regexp();
+
+  let mut part_list: Vec<Part> = vec![];
+  // Like the parser, fixed text without a modifier is collected until the
+  // next part, so that adjacent fixed text becomes a single part.
+  let mut pending_fixed_value = String::new();
+  let mut next_numeric_name = 0;
+  for part in parts {
+    if part.kind == PartType::FixedText {
+      let text = format!("{}{}{}", part.prefix, part.value, part.suffix);
+      if part.modifier == PartModifier::None {
+        pending_fixed_value.push_str(&text);
+        continue;
+      }
+      maybe_add_part_from_pending_fixed_value(
+        &mut part_list,
+        &mut pending_fixed_value,
+        encoding_callback,
+      )?;
+      if text.is_empty() {
+        continue;
+      }
+      let value = encoding_callback(&text)?;
+      part_list.push(Part::new(PartType::FixedText, value, part.modifier));
+      continue;
+    }
+
+    // Like the parser, treat a regexp that is the same as a wildcard as that
+    // wildcard.
+    let (kind, value) = match part.kind {
+      PartType::Regexp if part.value == segment_wildcard_regexp => {
+        (PartType::SegmentWildcard, String::new())
+      }
+      PartType::Regexp if part.value == FULL_WILDCARD_REGEXP_VALUE => {
+        (PartType::FullWildcard, String::new())
+      }
+      PartType::Regexp => {
+        validate_regex(&part.value).map_err(|reason| {
+          Error::Build(BuildError::InvalidRegex(part.value.clone(), reason))
+        })?;
+        (PartType::Regexp, part.value.clone())
+      }
+      kind => (kind, String::new()),
+    };
+
+    let name = match &part.name {
+      Some(name) => {
+        if !is_valid_name(name) {
+          return Err(Error::Build(BuildError::InvalidName(name.clone())));
+        }
+        if part_list.iter().any(|part| &part.name == name) {
+          return Err(Error::Build(BuildError::DuplicateName(name.clone())));
+        }
+        name.clone()
+      }
+      None => {
+        next_numeric_name += 1;
+        (next_numeric_name - 1).to_string()
+      }
+    };
+
+    // Like the parser, a prefix code point right before a part without a
+    // prefix or suffix becomes its prefix.
+    let mut prefix = part.prefix.as_str();
+    if prefix.is_empty()
+      && part.suffix.is_empty()
+      && !compile_options.prefix_code_point.is_empty()
+      && pending_fixed_value.ends_with(&compile_options.prefix_code_point)
+    {
+      let len =
+        pending_fixed_value.len() - compile_options.prefix_code_point.len();
+      pending_fixed_value.truncate(len);
+      prefix = &compile_options.prefix_code_point;
+    }
+    maybe_add_part_from_pending_fixed_value(
+      &mut part_list,
+      &mut pending_fixed_value,
+      encoding_callback,
+    )?;
+
+    part_list.push(Part {
+      kind,
+      value,
+      modifier: part.modifier,
+      name,
+      prefix: encode_affix(prefix, encoding_callback)?,
+      suffix: encode_affix(&part.suffix, encoding_callback)?,
+    });
+  }
+
+  maybe_add_part_from_pending_fixed_value(
+    &mut part_list,
+    &mut pending_fixed_value,
+    encoding_callback,
+  )?;
+
+  let pattern_string =
+    crate::component::generate_pattern_string(&part_list, &compile_options);
+  Component::from_parts(
+    kind,
+    &pattern_string,
+    part_list,
+    encoding_callback,
+    compile_options,
+  )
+  .optionally_transpose_regex_error(true)
+}
+
+fn maybe_add_part_from_pending_fixed_value(
+  part_list: &mut Vec<Part>,
+  pending_fixed_value: &mut String,
+  encoding_callback: fn(&str) -> Result<String, Error>,
+) -> Result<(), Error> {
+  if pending_fixed_value.is_empty() {
+    return Ok(());
+  }
+  let value = encoding_callback(&std::mem::take(pending_fixed_value))?;
+  part_list.push(Part::new(PartType::FixedText, value, PartModifier::None));
+  Ok(())
+}
+
+fn is_fixed(part: &PartBuilder) -> bool {
+  part.kind == PartType::FixedText
+    && part.modifier == PartModifier::None
+    && part.prefix.is_empty()
+    && part.suffix.is_empty()
+}
+
+fn encode_affix(
+  affix: &str,
+  encoding_callback: fn(&str) -> Result<String, Error>,
+) -> Result<String, Error> {
+  if affix.is_empty() {
+    return Ok(String::new());
+  }
+  encoding_callback(affix)
+}
+
+fn is_valid_name(name: &str) -> bool {
+  let mut chars = name.chars();
+  chars
+    .next()
+    .is_some_and(|first| is_valid_name_codepoint(first, true))
+    && chars.all(|char| is_valid_name_codepoint(char, false))
+}
+
+/// Check a regular expression with the tokenizer, like a regexp group in a
+/// pattern string.
+fn validate_regex(regex: &str) -> Result<(), &'static str> {
+  let input = format!("({regex})");
+  match tokenize(&input, TokenizePolicy::Strict) {
+    Ok(token_list) => match token_list.as_slice() {
+      [token, _] if token.kind == TokenType::Regexp && token.value == regex => {
+        Ok(())
+      }
+      _ => Err("unbalanced )"),
+    },
+    Err(Error::Tokenizer(TokenizerError::InvalidRegex(reason), _)) => {
+      Err(reason)
+    }
+    Err(_) => Err("incomplete escape code"),
+  }
+}
@@ -2,58 +2,167 @@
 
 use crate::Error;
 use crate::canonicalize_and_process::escape_pattern_string;
+use crate::error::Diagnostic;
+use crate::error::ErrorLocation;
+use crate::error::GenerateError;
+use crate::error::ParserError;
+use crate::error::RegExpError;
+use crate::matcher::Captures;
 use crate::matcher::InnerMatcher;
 use crate::matcher::Matcher;
+use crate::matcher::MatcherPart;
 use crate::parser::FULL_WILDCARD_REGEXP_VALUE;
+use crate::parser::MatchMode;
 use crate::parser::Options;
 use crate::parser::Part;
 use crate::parser::PartModifier;
 use crate::parser::PartType;
 use crate::parser::RegexSyntax;
 use crate::regexp::RegExp;
+use crate::regexp::RegExpFlags;
+use crate::tokenizer::TokenType;
+use crate::tokenizer::TokenizePolicy;
 use crate::tokenizer::is_valid_name_codepoint;
+use serde::Deserialize;
+use serde::Serialize;
+use std::borrow::Borrow;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Write;
+use std::hash::Hash;
+use std::ops::Range;
+use std::sync::Arc;
+use std::sync::OnceLock;
+
+/// The callback used to canonicalize the fixed text of a component.
+pub(crate) type EncodingCallback = fn(&str) -> Result<String, Error>;
+
+/// The components of a URL, and therefore of a URL pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentKind {
+  Protocol,
+  Username,
+  Password,
+  Hostname,
+  Port,
+  Pathname,
+  Search,
+  Hash,
+}
+
+impl std::fmt::Display for ComponentKind {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(match self {
+      ComponentKind::Protocol => "protocol",
+      ComponentKind::Username => "username",
+      ComponentKind::Password => "password",
+      ComponentKind::Hostname => "hostname",
+      ComponentKind::Port => "port",
+      ComponentKind::Pathname => "pathname",
+      ComponentKind::Search => "search",
+      ComponentKind::Hash => "hash",
+    })
+  }
+}
 
 // Ref: https://wicg.github.io/urlpattern/#component
+/// A compiled component of a pattern. Cloning a component is cheap, as the
+/// compiled regular expressions are shared between the clones.
 #[derive(Debug)]
 pub struct Component<R: RegExp> {
   pub pattern_string: String,
-  pub regexp: Result<R, Error>,
+  pub regexp: Result<Arc<R>, Error>,
   pub group_name_list: Vec<String>,
   pub matcher: Matcher<R>,
   pub has_regexp_group: bool,
+  pub(crate) part_list: Vec<Part>,
+  pub(crate) options: Options,
+  pub(crate) encoding_callback: EncodingCallback,
+  /// The regexps that group values are validated against when generating,
+  /// for every part that needs one. These are compiled on first use, and
+  /// shared between clones.
+  group_validators: Arc<OnceLock<Vec<Option<R>>>>,
+}
+
+// Note: a derived impl would require `R: Clone`, although the regexp is never
+// cloned itself.
+impl<R: RegExp> Clone for Component<R> {
+  fn clone(&self) -> Self {
+    Component {
+      pattern_string: self.pattern_string.clone(),
+      regexp: self.regexp.clone(),
+      group_name_list: self.group_name_list.clone(),
+      matcher: self.matcher.clone(),
+      has_regexp_group: self.has_regexp_group,
+      part_list: self.part_list.clone(),
+      options: self.options.clone(),
+      encoding_callback: self.encoding_callback,
+      group_validators: self.group_validators.clone(),
+    }
+  }
 }
 
 impl<R: RegExp> Component<R> {
   // Ref: https://wicg.github.io/urlpattern/#compile-a-component
-  pub(crate) fn compile<F>(
+  pub(crate) fn compile(
+    kind: ComponentKind,
     input: Option<&str>,
-    encoding_callback: F,
+    encoding_callback: EncodingCallback,
     options: Options,
-  ) -> Result<Self, Error>
-  where
-    F: Fn(&str) -> Result<String, Error>,
-  {
-    let part_list = crate::parser::parse_pattern_string(
-      input.unwrap_or("*"),
-      &options,
+  ) -> Result<Self, Error> {
+    let input = input.unwrap_or("*");
+    let part_list =
+      crate::parser::parse_pattern_string(input, &options, encoding_callback)
+        .map_err(|err| err.in_component(kind))?;
+    Ok(Self::from_parts(
+      kind,
+      input,
+      part_list,
       encoding_callback,
-    )?;
+      options,
+    ))
+  }
+
+  /// Compile a component from a part list. `input` is the pattern string the
+  /// part list was parsed from, which regexp errors point into.
+  pub(crate) fn from_parts(
+    kind: ComponentKind,
+    input: &str,
+    part_list: Vec<Part>,
+    encoding_callback: EncodingCallback,
+    options: Options,
+  ) -> Self {
     let (regexp_string, name_list) =
       generate_regular_expression_and_name_list(&part_list, &options);
-    let flags = if options.ignore_case { "ui" } else { "u" };
-    let mut regexp =
-      R::parse(&regexp_string, flags, false).map_err(Error::RegExp);
+    let flags = options.regexp_flags();
+    let regexp_error = |err: R::Error| {
+      regexp_error::<R>(input, &part_list, flags, err).in_component(kind)
+    };
+    let mut regexp = R::parse(&regexp_string, flags, false)
+      .map(Arc::new)
+      .map_err(regexp_error);
     if regexp.is_ok() && R::syntax() == RegexSyntax::EcmaScript {
       for part in part_list.iter() {
         if part.kind == PartType::Regexp {
-          regexp = R::parse(&regexp_string, flags, true).map_err(Error::RegExp);
+          regexp = R::parse(&regexp_string, flags, true)
+            .map(Arc::new)
+            .map_err(regexp_error);
         }
       }
     }
     let pattern_string = generate_pattern_string(&part_list, &options);
-    let matcher = generate_matcher::<R>(&part_list, &options, flags);
-    Ok(Component {
+    let matcher = match options.match_mode {
+      MatchMode::Full => {
+        generate_matcher::<R>(&part_list, &options, flags, regexp_error)
+      }
+      MatchMode::Prefix => {
+        generate_prefix_matcher(&part_list, &options, &regexp)
+      }
+    };
+    Component {
       pattern_string,
       regexp,
       group_name_list: name_list,
@@ -61,7 +170,31 @@ impl<R: RegExp> Component<R> {
       has_regexp_group: part_list
         .iter()
         .any(|part| part.kind == PartType::Regexp),
-    })
+      part_list,
+      options,
+      encoding_callback,
+      group_validators: Default::default(),
+    }
+  }
+
+  /// Compile the part list of this pathname component again with another
+  /// match mode.
+  pub(crate) fn with_match_mode(&self, match_mode: MatchMode) -> Self {
+    Self::from_parts(
+      ComponentKind::Pathname,
+      &self.pattern_string,
+      self.part_list.clone(),
+      self.encoding_callback,
+      Options {
+        match_mode,
+        ..self.options.clone()
+      },
+    )
+  }
+
+  /// The canonicalized part list of the pattern string of this component.
+  pub fn parts(&self) -> &[Part] {
+    &self.part_list
   }
 
   // Ref: https://wicg.github.io/urlpattern/#protocol-component-matches-a-special-scheme
@@ -77,18 +210,109 @@ impl<R: RegExp> Component<R> {
   }
 
   // Ref: https://wicg.github.io/urlpattern/#create-a-component-match-result
-  pub(crate) fn create_match_result(
+  pub(crate) fn create_match_result<'a>(
+    &'a self,
+    mut input: &'a str,
+    mut exec_result: Captures<'a>,
+  ) -> crate::UrlPatternComponentMatch<'a> {
+    // In prefix mode, the last capture is the remainder of the input, which
+    // is not part of the match.
+    if self.options.match_mode == MatchMode::Prefix {
+      let remainder = exec_result.pop().flatten().unwrap_or_default();
+      input = &input[..input.len() - remainder.len()];
+    }
+    crate::UrlPatternComponentMatch {
+      input,
+      names: &self.group_name_list,
+      values: exec_result,
+    }
+  }
+
+  /// Generate a string that this component matches, by substituting the given
+  /// group values into the part list. Values are canonicalized the same way
+  /// the fixed text of the pattern was, and are validated against the group
+  /// they are substituted into.
+  ///
+  /// Optional fixed text is omitted. Groups without a value are omitted if
+  /// they are optional, and are an error otherwise.
+  pub(crate) fn generate<K, V>(
     &self,
-    input: String,
-    exec_result: Vec<Option<&str>>,
-  ) -> crate::UrlPatternComponentResult {
-    let groups = self
-      .group_name_list
-      .clone()
-      .into_iter()
-      .zip(exec_result.into_iter().map(|s| s.map(str::to_owned)))
-      .collect();
-    crate::UrlPatternComponentResult { input, groups }
+    groups: &HashMap<K, V>,
+  ) -> Result<String, Error>
+  where
+    K: Borrow<str> + Hash + Eq,
+    V: AsRef<str>,
+  {
+    let mut result = String::new();
+    for (index, part) in self.part_list.iter().enumerate() {
+      if part.kind == PartType::FixedText {
+        if matches!(part.modifier, PartModifier::None | PartModifier::OneOrMore)
+        {
+          result.push_str(&part.value);
+        }
+        continue;
+      }
+      let Some(value) = groups.get(part.name.as_str()) else {
+        if matches!(
+          part.modifier,
+          PartModifier::Optional | PartModifier::ZeroOrMore
+        ) {
+          continue;
+        }
+        return Err(Error::Generate(GenerateError::MissingGroup(
+          part.name.clone(),
+        )));
+      };
+      let value = (self.encoding_callback)(value.as_ref())?;
+      if !self.group_value_is_valid(index, part, &value) {
+        return Err(Error::Generate(GenerateError::InvalidGroupValue(
+          part.name.clone(),
+          value,
+        )));
+      }
+      result.push_str(&part.prefix);
+      result.push_str(&value);
+      result.push_str(&part.suffix);
+    }
+    Ok(result)
+  }
+
+  /// Whether the canonicalized value could have been captured by the group
+  /// for the part at the given index.
+  fn group_value_is_valid(
+    &self,
+    index: usize,
+    part: &Part,
+    value: &str,
+  ) -> bool {
+    if part.modifier == PartModifier::None {
+      match part.kind {
+        PartType::FullWildcard => return true,
+        PartType::SegmentWildcard => {
+          return !value.is_empty()
+            && self
+              .options
+              .delimiter_code_point
+              .is_none_or(|delimiter| !value.contains(delimiter));
+        }
+        PartType::FixedText | PartType::Regexp => {}
+      }
+    }
+
+    let group_validators = self.group_validators.get_or_init(|| {
+      let flags = self.options.regexp_flags();
+      self
+        .part_list
+        .iter()
+        .map(|part| {
+          let regexp = group_validator_regexp(part, &self.options)?;
+          R::parse(&regexp, flags, false).ok()
+        })
+        .collect()
+    });
+    group_validators[index]
+      .as_ref()
+      .is_some_and(|regexp| regexp.matches(value).is_some())
   }
 
   pub(crate) fn optionally_transpose_regex_error(
@@ -100,6 +324,239 @@ impl<R: RegExp> Component<R> {
     }
     Ok(self)
   }
+
+  /// Compile the component that matches this component followed by the
+  /// pattern string `input`. A trailing full wildcard of this component is
+  /// replaced by `input`, and a delimiter at the end of this component and the
+  /// start of `input` is only matched once.
+  pub(crate) fn join(
+    &self,
+    kind: ComponentKind,
+    input: &str,
+  ) -> Result<Self, Error> {
+    let mut sub_part_list = crate::parser::parse_pattern_string(
+      input,
+      &self.options,
+      self.encoding_callback,
+    )
+    .map_err(|err| err.in_component(kind))?;
+
+    let mut part_list = self.part_list.clone();
+    if let Some(last) = part_list.last() {
+      if last.kind == PartType::FullWildcard
+        && last.modifier == PartModifier::None
+      {
+        // Note: the prefix of the wildcard is kept, so that `/app/*` joined
+        // with `settings` is `/app/settings`.
+        let prefix = part_list.pop().unwrap().prefix;
+        part_list.push(Part::new(
+          PartType::FixedText,
+          prefix,
+          PartModifier::None,
+        ));
+      }
+    }
+
+    if let (Some(delimiter), Some(last), Some(first)) = (
+      self.options.delimiter_code_point,
+      part_list.last_mut(),
+      sub_part_list.first(),
+    ) {
+      let first_text = if is_literal(first) {
+        &first.value
+      } else {
+        &first.prefix
+      };
+      if is_literal(last)
+        && last.value.ends_with(delimiter)
+        && first_text.starts_with(delimiter)
+      {
+        last.value.pop();
+      }
+    }
+
+    // A group directly after a prefix code point takes it as its prefix, like
+    // parsing `/*` does.
+    let prefix_code_point = &self.options.prefix_code_point;
+    if let (Some(last), Some(first)) =
+      (part_list.last_mut(), sub_part_list.first_mut())
+    {
+      if is_literal(last)
+        && first.kind != PartType::FixedText
+        && first.modifier == PartModifier::None
+        && first.prefix.is_empty()
+        && !prefix_code_point.is_empty()
+        && last.value.ends_with(prefix_code_point.as_str())
+      {
+        last
+          .value
+          .truncate(last.value.len() - prefix_code_point.len());
+        first.prefix = prefix_code_point.clone();
+      }
+    }
+
+    part_list.extend(sub_part_list);
+    part_list.retain(|part| !is_literal(part) || !part.value.is_empty());
+
+    // The unnamed groups of both part lists are numbered from the start again,
+    // and named groups must be unique across both.
+    let mut next_numeric_name = 0;
+    let mut names = HashSet::new();
+    for part in &mut part_list {
+      if part.kind == PartType::FixedText {
+        continue;
+      }
+      if part.name.starts_with(|c: char| c.is_ascii_digit()) {
+        part.name = next_numeric_name.to_string();
+        next_numeric_name += 1;
+      } else if !names.insert(part.name.clone()) {
+        return Err(
+          Error::Parser(
+            ParserError::DuplicateName(part.name.clone()),
+            ErrorLocation::new(token_span(input, TokenType::Name, &part.name)),
+          )
+          .in_component(kind),
+        );
+      }
+    }
+
+    // Note: the pattern string is parsed again, so that the joined component
+    // has the same part list as parsing the joined pattern would give.
+    let pattern_string = generate_pattern_string(&part_list, &self.options);
+    Self::compile(
+      kind,
+      Some(&pattern_string),
+      self.encoding_callback,
+      self.options.clone(),
+    )?
+    .optionally_transpose_regex_error(true)
+  }
+}
+
+/// The regexp that a value for the group of a part is validated against when
+/// generating: the same expression the group uses in the generated regular
+/// expression. Fixed text and wildcards without a modifier are validated
+/// without a regexp.
+fn group_validator_regexp(part: &Part, options: &Options) -> Option<String> {
+  let regexp_value = match part.kind {
+    PartType::FixedText => return None,
+    PartType::SegmentWildcard | PartType::FullWildcard
+      if part.modifier == PartModifier::None =>
+    {
+      return None;
+    }
+    PartType::SegmentWildcard => options.generate_segment_wildcard_regexp(),
+    PartType::FullWildcard => FULL_WILDCARD_REGEXP_VALUE.to_string(),
+    PartType::Regexp => part.value.clone(),
+  };
+  let group = match &part.modifier {
+    PartModifier::None | PartModifier::Optional => regexp_value,
+    _ if !part.prefix.is_empty() || !part.suffix.is_empty() => format!(
+      "(?:{regexp_value})(?:{}{}(?:{regexp_value}))*",
+      options.escape_regexp_string(&part.suffix),
+      options.escape_regexp_string(&part.prefix),
+    ),
+    modifier => format!("(?:{regexp_value}){modifier}"),
+  };
+  Some(format!("^(?:{group})$"))
+}
+
+/// Parse the pattern string of a single component into its canonicalized part
+/// list, like [Component::parts] of a compiled pattern. The pathname is parsed
+/// as a hierarchical path, and not as an opaque path.
+///
+/// # Examples
+///
+/// ```
+/// use urlpattern::ComponentKind;
+/// use urlpattern::component::parse_component;
+/// use urlpattern::parser::PartModifier;
+///
+///# fn main() {
+/// let parts = parse_component(ComponentKind::Pathname, "/users/:id?").unwrap();
+/// assert_eq!(parts[0].value, "/users");
+/// assert_eq!(parts[1].name, "id");
+/// assert_eq!(parts[1].prefix, "/");
+/// assert_eq!(parts[1].modifier, PartModifier::Optional);
+///# }
+/// ```
+pub fn parse_component(
+  kind: ComponentKind,
+  input: &str,
+) -> Result<Vec<Part>, Error> {
+  let (encoding_callback, options) =
+    crate::component_options(kind, input, &Default::default(), false);
+  crate::parser::parse_pattern_string(input, &options, encoding_callback)
+    .map_err(|err| err.in_component(kind))
+}
+
+/// Compares two part lists by specificity, as done by the
+/// `URLPattern.compareComponent()` static method.
+pub(crate) fn compare_part_lists(left: &[Part], right: &[Part]) -> Ordering {
+  for (left, right) in left.iter().zip(right) {
+    let ordering = compare_parts(left, right);
+    if ordering != Ordering::Equal {
+      return ordering;
+    }
+  }
+  // If one list is a prefix of the other, the next part of the longer list is
+  // compared against an empty fixed text part.
+  let empty = Part::new(PartType::FixedText, String::new(), PartModifier::None);
+  match left.len().cmp(&right.len()) {
+    Ordering::Equal => Ordering::Equal,
+    Ordering::Less => compare_parts(&empty, &right[left.len()]),
+    Ordering::Greater => compare_parts(&left[right.len()], &empty),
+  }
+}
+
+/// Returns whether two part lists are the same apart from their group names,
+/// meaning that they match exactly the same inputs.
+pub(crate) fn part_lists_structurally_equivalent(
+  left: &[Part],
+  right: &[Part],
+) -> bool {
+  left.len() == right.len()
+    && left.iter().zip(right).all(|(left, right)| {
+      left.kind == right.kind
+        && left.value == right.value
+        && left.modifier == right.modifier
+        && left.prefix == right.prefix
+        && left.suffix == right.suffix
+    })
+}
+
+/// Parts are ordered by their type first (fixed text > regexp > segment
+/// wildcard > full wildcard), then by their modifier (none > one or more >
+/// optional > zero or more), and finally lexicographically by their prefix,
+/// value, and suffix. The strings have all been canonicalized, so a simple
+/// byte-wise comparison is sufficient.
+fn compare_parts(left: &Part, right: &Part) -> Ordering {
+  fn kind_rank(kind: &PartType) -> u8 {
+    match kind {
+      PartType::FullWildcard => 0,
+      PartType::SegmentWildcard => 1,
+      PartType::Regexp => 2,
+      PartType::FixedText => 3,
+    }
+  }
+
+  fn modifier_rank(modifier: &PartModifier) -> u8 {
+    match modifier {
+      PartModifier::ZeroOrMore => 0,
+      PartModifier::Optional => 1,
+      PartModifier::OneOrMore => 2,
+      PartModifier::None => 3,
+    }
+  }
+
+  kind_rank(&left.kind)
+    .cmp(&kind_rank(&right.kind))
+    .then_with(|| {
+      modifier_rank(&left.modifier).cmp(&modifier_rank(&right.modifier))
+    })
+    .then_with(|| left.prefix.cmp(&right.prefix))
+    .then_with(|| left.value.cmp(&right.value))
+    .then_with(|| left.suffix.cmp(&right.suffix))
 }
 
 // Ref: https://wicg.github.io/urlpattern/#generate-a-regular-expression-and-name-list
@@ -107,9 +564,29 @@ fn generate_regular_expression_and_name_list(
   part_list: &[Part],
   options: &Options,
 ) -> (String, Vec<String>) {
+  let (part_list, remainder) = match options.match_mode {
+    MatchMode::Full => (Cow::Borrowed(part_list), None),
+    MatchMode::Prefix => {
+      let (part_list, required) = split_prefix_remainder(part_list, options);
+      let remainder = match options.delimiter_code_point {
+        Some(delimiter) => {
+          let mut buffer = [0; 4];
+          let delimiter =
+            options.escape_regexp_string(delimiter.encode_utf8(&mut buffer));
+          if required {
+            format!("{delimiter}.*")
+          } else {
+            format!("(?:{delimiter}.*)?")
+          }
+        }
+        None => ".*".to_owned(),
+      };
+      (part_list, Some(remainder))
+    }
+  };
   let mut result = String::from("^");
   let mut name_list = vec![];
-  for part in part_list {
+  for part in part_list.iter() {
     if part.kind == PartType::FixedText {
       if part.modifier == PartModifier::None {
         result.push_str(&options.escape_regexp_string(&part.value));
@@ -173,12 +650,43 @@ fn generate_regular_expression_and_name_list(
     )
     .unwrap();
   }
+  if let Some(remainder) = remainder {
+    // Note: the remainder is captured after all groups, but is not one of
+    // them, so it is not part of the name list.
+    write!(result, "({remainder})").unwrap();
+  }
   result.push('$');
   (result, name_list)
 }
 
+/// Split the remainder of an input in [MatchMode::Prefix] off a part list.
+/// The remainder is empty, or starts with the delimiter. If the part list
+/// itself ends with the delimiter, that delimiter becomes the start of the
+/// remainder instead, so that `/api/` matches `/api/v2` with a remainder of
+/// `/v2`. Returns whether the remainder is required to start with the
+/// delimiter for that reason.
+fn split_prefix_remainder<'a>(
+  part_list: &'a [Part],
+  options: &Options,
+) -> (Cow<'a, [Part]>, bool) {
+  let Some(delimiter) = options.delimiter_code_point else {
+    return (Cow::Borrowed(part_list), false);
+  };
+  match part_list.last() {
+    Some(part) if is_literal(part) && part.value.ends_with(delimiter) => {
+      let mut part_list = part_list.to_vec();
+      part_list.last_mut().unwrap().value.pop();
+      (Cow::Owned(part_list), true)
+    }
+    _ => (Cow::Borrowed(part_list), false),
+  }
+}
+
 // Ref: https://wicg.github.io/urlpattern/#generate-a-pattern-string
-fn generate_pattern_string(part_list: &[Part], options: &Options) -> String {
+pub(crate) fn generate_pattern_string(
+  part_list: &[Part],
+  options: &Options,
+) -> String {
   let mut result = String::new();
   for (i, part) in part_list.iter().enumerate() {
     let prev_part: Option<&Part> =
@@ -278,19 +786,126 @@ fn generate_pattern_string(part_list: &[Part], options: &Options) -> String {
   result
 }
 
+/// Parse the pattern string of a component like [Component::compile], but
+/// recover from all errors. Returns a best-effort part list together with every
+/// error and warning found in the pattern string, ordered by their position.
+/// Regexp groups are compiled on their own, so that every invalid group is
+/// reported.
+pub(crate) fn diagnose<R: RegExp>(
+  kind: ComponentKind,
+  input: &str,
+  encoding_callback: EncodingCallback,
+  options: &Options,
+) -> (Vec<Part>, Vec<Diagnostic>) {
+  let (part_list, mut diagnostics) =
+    crate::parser::parse_pattern_string_with_diagnostics(
+      input,
+      options,
+      encoding_callback,
+    );
+  let flags = options.regexp_flags();
+  for part in &part_list {
+    if let Some(err) = regexp_group_error::<R>(input, part, flags) {
+      diagnostics.push(err.into());
+    }
+  }
+  for diagnostic in &mut diagnostics {
+    if let Some(location) = &mut diagnostic.location {
+      location.component = Some(kind);
+    }
+  }
+  diagnostics.sort_by_key(|diagnostic| {
+    diagnostic
+      .location
+      .as_ref()
+      .map_or(0, |location| location.span.start)
+  });
+  (part_list, diagnostics)
+}
+
+/// Create the error for a regular expression of a component that failed to
+/// compile. To point at the cause, the regexp groups are compiled on their own,
+/// and the first group that is not valid by itself is reported.
+fn regexp_error<R: RegExp>(
+  input: &str,
+  part_list: &[Part],
+  flags: RegExpFlags,
+  err: R::Error,
+) -> Error {
+  part_list
+    .iter()
+    .find_map(|part| regexp_group_error::<R>(input, part, flags))
+    .unwrap_or_else(|| {
+      Error::RegExp(
+        RegExpError {
+          group: None,
+          message: err.to_string(),
+        },
+        ErrorLocation::new(0..input.len()),
+      )
+    })
+}
+
+/// The error for a regexp group that is not a valid regular expression by
+/// itself, if the part is such a group.
+fn regexp_group_error<R: RegExp>(
+  input: &str,
+  part: &Part,
+  flags: RegExpFlags,
+) -> Option<Error> {
+  if part.kind != PartType::Regexp {
+    return None;
+  }
+  let err = R::parse(&format!("^(?:{})$", part.value), flags, true).err()?;
+  Some(Error::RegExp(
+    RegExpError {
+      group: Some(part.name.clone()),
+      message: err.to_string(),
+    },
+    ErrorLocation::new(token_span(input, TokenType::Regexp, &part.value)),
+  ))
+}
+
+/// The span of the token of the given kind and value in the input, or the
+/// whole input if there is no such token.
+fn token_span(input: &str, kind: TokenType, value: &str) -> Range<usize> {
+  crate::tokenizer::tokenize(input, TokenizePolicy::Lenient)
+    .ok()
+    .and_then(|tokens| {
+      tokens
+        .into_iter()
+        .find(|token| token.kind == kind && token.value == value)
+    })
+    .map_or(0..input.len(), |token| token.index..token.end)
+}
+
+/// Whether the part is fixed text that is always matched.
+fn is_literal(part: &Part) -> bool {
+  part.kind == PartType::FixedText && part.modifier == PartModifier::None
+}
+
+/// Whether the fixed text of the part can be matched case insensitively
+/// without a regexp. See [crate::matcher::folds_like_ascii].
+pub(crate) fn folds_like_ascii(part: &Part) -> bool {
+  use crate::matcher::folds_like_ascii;
+  folds_like_ascii(&part.prefix)
+    && folds_like_ascii(&part.suffix)
+    && (part.kind != PartType::FixedText || folds_like_ascii(&part.value))
+}
+
 /// This function generates a matcher for a given parts list.
 fn generate_matcher<R: RegExp>(
   mut part_list: &[Part],
   options: &Options,
-  flags: &str,
+  flags: RegExpFlags,
+  regexp_error: impl Fn(R::Error) -> Error,
 ) -> Matcher<R> {
-  fn is_literal(part: &Part) -> bool {
-    part.kind == PartType::FixedText && part.modifier == PartModifier::None
-  }
-
+  // The matchers other than the regexp matcher ignore the case of ASCII
+  // letters only, so other fixed text is always matched with the regexp.
+  let fast = !options.ignore_case || part_list.iter().all(folds_like_ascii);
   // If the first part is a fixed string, we can use it as a literal prefix.
   let mut prefix = match part_list.first() {
-    Some(part) if is_literal(part) => {
+    Some(part) if fast && is_literal(part) => {
       part_list = &part_list[1..];
       part.value.clone()
     }
@@ -298,7 +913,7 @@ fn generate_matcher<R: RegExp>(
   };
   // If the last part is a fixed string, we can use it as a literal suffix.
   let mut suffix = match part_list.last() {
-    Some(part) if is_literal(part) => {
+    Some(part) if fast && is_literal(part) => {
       part_list = &part_list[..part_list.len() - 1];
       part.value.clone()
     }
@@ -322,7 +937,8 @@ fn generate_matcher<R: RegExp>(
     // If there is only one part, and it is a simple full wildcard with no
     // prefix or suffix, we can use a simple wildcard matcher.
     [part]
-      if part.kind == PartType::FullWildcard
+      if fast
+        && part.kind == PartType::FullWildcard
         && part.modifier == PartModifier::None =>
     {
       prefix += &part.prefix;
@@ -337,7 +953,8 @@ fn generate_matcher<R: RegExp>(
     // If there is only one part, and it is a simple segment wildcard with no
     // prefix or suffix, we can use a simple wildcard matcher.
     [part]
-      if part.kind == PartType::SegmentWildcard
+      if fast
+        && part.kind == PartType::SegmentWildcard
         && part.modifier == PartModifier::None =>
     {
       prefix += &part.prefix;
@@ -349,12 +966,24 @@ fn generate_matcher<R: RegExp>(
         allow_empty: false,
       }
     }
+    // If all parts are fixed text, segment wildcards or simple full
+    // wildcards, we can match them piece by piece, without a regexp.
+    part_list if fast && part_list.iter().all(is_multi_capture_part) => {
+      let parts = generate_matcher_parts(part_list);
+      let filter = options.delimiter_code_point;
+      InnerMatcher::MultiCapture {
+        linear: crate::matcher::is_linear(&parts, filter),
+        parts,
+        filter,
+      }
+    }
     // For all other cases, we fall back to a regexp matcher.
     part_list => {
       let (regexp_string, _) =
         generate_regular_expression_and_name_list(part_list, options);
-      let regexp =
-        R::parse(&regexp_string, flags, false).map_err(Error::RegExp);
+      let regexp = R::parse(&regexp_string, flags, false)
+        .map(Arc::new)
+        .map_err(regexp_error);
       InnerMatcher::RegExp { regexp }
     }
   };
@@ -366,3 +995,106 @@ fn generate_matcher<R: RegExp>(
     ignore_case: options.ignore_case,
   }
 }
+
+/// Generate the matcher of a component in [MatchMode::Prefix]. The remainder
+/// of the input is matched by a trailing [MatcherPart::Remainder], or by a
+/// full wildcard if there is no delimiter. Part lists that can not be matched
+/// piece by piece are matched with the regexp of the component, which also
+/// captures the remainder.
+fn generate_prefix_matcher<R: RegExp>(
+  part_list: &[Part],
+  options: &Options,
+  regexp: &Result<Arc<R>, Error>,
+) -> Matcher<R> {
+  let (part_list, required) = split_prefix_remainder(part_list, options);
+  let fast = !options.ignore_case || part_list.iter().all(folds_like_ascii);
+  if !fast || !part_list.iter().all(is_multi_capture_part) {
+    return Matcher {
+      prefix: String::new(),
+      suffix: String::new(),
+      inner: InnerMatcher::RegExp {
+        regexp: regexp.clone(),
+      },
+      ignore_case: options.ignore_case,
+    };
+  }
+
+  // Note: the suffix of a prefix matcher is always the remainder, so only
+  // the fixed text at the start can be split off.
+  let (prefix, part_list) = match part_list.split_first() {
+    Some((part, rest)) if is_literal(part) => (part.value.clone(), rest),
+    _ => (String::new(), &part_list[..]),
+  };
+  let mut parts = generate_matcher_parts(part_list);
+  let filter = options.delimiter_code_point;
+  parts.push(match filter {
+    Some(_) => MatcherPart::Remainder { required },
+    None => MatcherPart::FullWildcard,
+  });
+  Matcher {
+    prefix,
+    suffix: String::new(),
+    inner: InnerMatcher::MultiCapture {
+      linear: crate::matcher::is_linear(&parts, filter),
+      parts,
+      filter,
+    },
+    ignore_case: options.ignore_case,
+  }
+}
+
+/// Whether the part can be matched by an [InnerMatcher::MultiCapture]
+/// matcher: fixed text, segment wildcards or simple full wildcards.
+fn is_multi_capture_part(part: &Part) -> bool {
+  match part.kind {
+    PartType::FixedText => {
+      matches!(part.modifier, PartModifier::None | PartModifier::Optional)
+    }
+    PartType::SegmentWildcard => true,
+    PartType::FullWildcard => part.modifier == PartModifier::None,
+    PartType::Regexp => false,
+  }
+}
+
+/// Convert a list of fixed text and wildcard parts into the parts of a
+/// [InnerMatcher::MultiCapture] matcher. The prefix and suffix of wildcards
+/// without a modifier become literals, and adjacent literals are merged.
+fn generate_matcher_parts(part_list: &[Part]) -> Vec<MatcherPart> {
+  fn push_literal(parts: &mut Vec<MatcherPart>, value: &str) {
+    if value.is_empty() {
+      return;
+    }
+    match parts.last_mut() {
+      Some(MatcherPart::Literal(literal)) => literal.push_str(value),
+      _ => parts.push(MatcherPart::Literal(value.to_owned())),
+    }
+  }
+
+  let mut parts = vec![];
+  for part in part_list {
+    match part.kind {
+      PartType::FixedText if part.modifier == PartModifier::Optional => {
+        parts.push(MatcherPart::OptionalLiteral(part.value.clone()));
+      }
+      PartType::FixedText => push_literal(&mut parts, &part.value),
+      PartType::SegmentWildcard if part.modifier != PartModifier::None => {
+        parts.push(MatcherPart::SegmentGroup {
+          prefix: part.prefix.clone(),
+          suffix: part.suffix.clone(),
+          modifier: part.modifier,
+        });
+      }
+      PartType::SegmentWildcard | PartType::FullWildcard => {
+        push_literal(&mut parts, &part.prefix);
+        parts.push(if part.kind == PartType::SegmentWildcard {
+          MatcherPart::SegmentWildcard
+        } else {
+          MatcherPart::FullWildcard
+        });
+        push_literal(&mut parts, &part.suffix);
+      }
+      PartType::Regexp => unreachable!(),
+    }
+  }
+  parts
+}
@@ -1,6 +1,10 @@
 // Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
 
+use std::collections::HashMap;
+
 use crate::UrlPatternInit;
+use crate::canonicalize_and_process::escape_pattern_string;
+use crate::component::ComponentKind;
 use crate::error::Error;
 use crate::regexp::RegExp;
 use crate::tokenizer::Token;
@@ -22,11 +26,34 @@ enum ConstructorStringParserState {
   Done,
 }
 
+impl ConstructorStringParserState {
+  /// The component whose string is collected in this state.
+  fn component(&self) -> Option<ComponentKind> {
+    match self {
+      ConstructorStringParserState::Protocol => Some(ComponentKind::Protocol),
+      ConstructorStringParserState::Username => Some(ComponentKind::Username),
+      ConstructorStringParserState::Password => Some(ComponentKind::Password),
+      ConstructorStringParserState::Hostname => Some(ComponentKind::Hostname),
+      ConstructorStringParserState::Port => Some(ComponentKind::Port),
+      ConstructorStringParserState::Pathname => Some(ComponentKind::Pathname),
+      ConstructorStringParserState::Search => Some(ComponentKind::Search),
+      ConstructorStringParserState::Hash => Some(ComponentKind::Hash),
+      ConstructorStringParserState::Init
+      | ConstructorStringParserState::Authority
+      | ConstructorStringParserState::Done => None,
+    }
+  }
+}
+
+/// The byte offset of the string of every component in a constructor string.
+pub(crate) type ComponentOffsets = HashMap<ComponentKind, usize>;
+
 // Ref: https://wicg.github.io/urlpattern/#constructor-string-parser
 struct ConstructorStringParser<'a> {
   input: &'a str,
   token_list: Vec<Token<'a>>,
   result: UrlPatternInit,
+  component_offsets: ComponentOffsets,
   component_start: usize,
   token_index: usize,
   token_increment: usize,
@@ -161,6 +188,10 @@ impl<'a> ConstructorStringParser<'a> {
       | ConstructorStringParserState::Authority
       | ConstructorStringParserState::Done => {}
     }
+    if let Some(component) = self.state.component() {
+      let offset = self.get_safe_token(self.component_start).index;
+      self.component_offsets.insert(component, offset);
+    }
 
     if self.state != ConstructorStringParserState::Init
       && new_state != ConstructorStringParserState::Done
@@ -259,10 +290,19 @@ impl<'a> ConstructorStringParser<'a> {
   ) -> Result<(), Error> {
     let protocol_string = self.make_component_string();
     let protocol_component = crate::component::Component::<R>::compile(
+      ComponentKind::Protocol,
       Some(&protocol_string),
       crate::canonicalize_and_process::canonicalize_protocol,
       Default::default(),
-    )?;
+    )
+    .map_err(|mut err| {
+      if let Some(location) = err.location_mut() {
+        let offset = self.get_safe_token(self.component_start).index;
+        location.span =
+          location.span.start + offset..location.span.end + offset;
+      }
+      err
+    })?;
     if protocol_component.protocol_component_matches_special_scheme() {
       self.protocol_matches_special_scheme = true;
     }
@@ -293,9 +333,11 @@ impl<'a> ConstructorStringParser<'a> {
 }
 
 // Ref: https://wicg.github.io/urlpattern/#parse-a-constructor-string
+/// Parse a constructor string into its components. Also returns where the
+/// string of each component starts in the input.
 pub(crate) fn parse_constructor_string<R: RegExp>(
   input: &str,
-) -> Result<UrlPatternInit, Error> {
+) -> Result<(UrlPatternInit, ComponentOffsets), Error> {
   let token_list = crate::tokenizer::tokenize(
     input,
     crate::tokenizer::TokenizePolicy::Lenient,
@@ -315,6 +357,7 @@ pub(crate) fn parse_constructor_string<R: RegExp>(
       hash: None,
       base_url: None,
     },
+    component_offsets: HashMap::new(),
     component_start: 0,
     token_index: 0,
     token_increment: 1,
@@ -450,5 +493,91 @@ pub(crate) fn parse_constructor_string<R: RegExp>(
     parser.result.port = Some(String::new());
   }
 
-  Ok(parser.result)
+  Ok((parser.result, parser.component_offsets))
+}
+
+/// Escape the pattern string of a component for use in a constructor string.
+/// Characters that the constructor string parser would read as the start of a
+/// later component are wrapped in a group, which does not change the pattern
+/// they are parsed into.
+pub(crate) fn escape_component_string(
+  kind: ComponentKind,
+  pattern: &str,
+) -> String {
+  let special: &[&str] = match kind {
+    ComponentKind::Protocol => &[":"],
+    ComponentKind::Username
+    | ComponentKind::Password
+    | ComponentKind::Hostname => &[":", "@", "/", "?", "#"],
+    ComponentKind::Port => &["/", "?", "#"],
+    ComponentKind::Pathname => &["?", "#"],
+    ComponentKind::Search => &["#"],
+    ComponentKind::Hash => &[],
+  };
+  let token_list = match crate::tokenizer::tokenize(
+    pattern,
+    crate::tokenizer::TokenizePolicy::Lenient,
+  ) {
+    Ok(token_list) => token_list,
+    Err(_) => return pattern.to_owned(),
+  };
+
+  let mut result = String::new();
+  let mut group_depth = 0usize;
+  let mut hostname_ipv6_bracket_depth = 0usize;
+  for token in token_list {
+    match token.kind {
+      TokenType::Open => group_depth += 1,
+      TokenType::Close => group_depth = group_depth.saturating_sub(1),
+      TokenType::Char | TokenType::EscapedChar | TokenType::InvalidChar
+        if group_depth == 0 =>
+      {
+        if kind == ComponentKind::Hostname {
+          match token.value {
+            "[" => hostname_ipv6_bracket_depth += 1,
+            "]" => {
+              hostname_ipv6_bracket_depth =
+                hostname_ipv6_bracket_depth.saturating_sub(1)
+            }
+            _ => {}
+          }
+        }
+        // The colons of an IPv6 address are not a port prefix.
+        let in_ipv6_address =
+          hostname_ipv6_bracket_depth > 0 && token.value == ":";
+        if special.contains(&token.value) && !in_ipv6_address {
+          result.push('{');
+          result.push_str(&escape_pattern_string(token.value));
+          result.push('}');
+          continue;
+        }
+      }
+      _ => {}
+    }
+    result.push_str(&pattern[token.index..token.end]);
+  }
+  result
+}
+
+/// Whether the last token of a constructor string is a group, which a `?`
+/// after it would be a modifier of.
+pub(crate) fn ends_with_group(input: &str) -> bool {
+  let token_list = crate::tokenizer::tokenize(
+    input,
+    crate::tokenizer::TokenizePolicy::Lenient,
+  )
+  .unwrap_or_default();
+  token_list
+    .iter()
+    .rev()
+    .find(|token| token.kind != TokenType::End)
+    .is_some_and(|token| {
+      matches!(
+        token.kind,
+        TokenType::Name
+          | TokenType::Regexp
+          | TokenType::Close
+          | TokenType::Asterisk
+      )
+    })
 }
@@ -0,0 +1,50 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use std::collections::HashMap;
+
+use crate::ComponentKind;
+use crate::error::Diagnostic;
+use crate::error::Severity;
+use crate::parser::Part;
+
+/// The result of diagnosing a pattern with [crate::UrlPattern::diagnose].
+///
+/// Unlike parsing, diagnosing does not stop at the first error. Every
+/// component is parsed with error recovery, so that all errors and warnings of
+/// the pattern are reported at once.
+#[derive(Debug)]
+pub struct PatternDiagnostics {
+  /// The best-effort part list of every component that could be processed.
+  /// Text that could not be parsed is treated as fixed text.
+  pub parts: HashMap<ComponentKind, Vec<Part>>,
+  /// All errors and warnings found in the pattern, in component order.
+  pub diagnostics: Vec<Diagnostic>,
+}
+
+impl PatternDiagnostics {
+  /// Returns whether any of the diagnostics is an error, meaning that parsing
+  /// the pattern fails.
+  pub fn has_errors(&self) -> bool {
+    self.errors().next().is_some()
+  }
+
+  /// The diagnostics that are errors.
+  pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+    self.with_severity(Severity::Error)
+  }
+
+  /// The diagnostics that are warnings.
+  pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+    self.with_severity(Severity::Warning)
+  }
+
+  fn with_severity(
+    &self,
+    severity: Severity,
+  ) -> impl Iterator<Item = &Diagnostic> {
+    self
+      .diagnostics
+      .iter()
+      .filter(move |diagnostic| diagnostic.severity == severity)
+  }
+}
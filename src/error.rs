@@ -1,16 +1,122 @@
 use std::fmt;
+use std::fmt::Write;
+use std::ops::Range;
 
+use crate::component::ComponentKind;
 use crate::tokenizer::TokenType;
 
 /// A error occurring during URL pattern construction, or matching.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Error {
   BaseUrlRequired,
   BaseUrlWithInit,
-  Tokenizer(TokenizerError, usize),
-  Parser(ParserError),
+  Tokenizer(TokenizerError, ErrorLocation),
+  Parser(ParserError, ErrorLocation),
   Url(url::ParseError),
-  RegExp(()),
+  /// Fixed text in the pattern string of a component could not be
+  /// canonicalized.
+  Canonicalize(url::ParseError, ErrorLocation),
+  RegExp(RegExpError, ErrorLocation),
+  Generate(GenerateError),
+  /// A part given to [crate::builder::UrlPatternBuilder] is not valid.
+  Build(BuildError),
+  /// The name of a component is not one of the names of [ComponentKind].
+  UnknownComponent(String),
+}
+
+impl Error {
+  /// A stable code identifying the kind of error, for example
+  /// `duplicate-name`. Unlike the message, the code does not change between
+  /// versions, and every kind of error has its own code.
+  pub fn code(&self) -> &'static str {
+    match self {
+      Error::BaseUrlRequired => "base-url-required",
+      Error::BaseUrlWithInit => "base-url-with-init",
+      Error::Tokenizer(TokenizerError::IncompleteEscapeCode, _) => {
+        "incomplete-escape-code"
+      }
+      Error::Tokenizer(TokenizerError::InvalidName, _) => "invalid-name",
+      Error::Tokenizer(TokenizerError::InvalidRegex(_), _) => {
+        "malformed-regexp-group"
+      }
+      Error::Parser(ParserError::ExpectedToken(..), _) => "unexpected-token",
+      Error::Parser(ParserError::DuplicateName(_), _) => "duplicate-name",
+      Error::Url(_) => "invalid-url",
+      Error::Canonicalize(..) => "invalid-component",
+      Error::RegExp(..) => "regexp-compile-error",
+      Error::Generate(GenerateError::MissingGroup(_)) => "missing-group",
+      Error::Generate(GenerateError::InvalidGroupValue(..)) => {
+        "invalid-group-value"
+      }
+      Error::Generate(GenerateError::Unmatched(_)) => "unmatched",
+      Error::Build(BuildError::InvalidName(_)) => "invalid-part-name",
+      Error::Build(BuildError::InvalidRegex(..)) => "invalid-part-regexp",
+      Error::Build(BuildError::DuplicateName(_)) => "duplicate-part-name",
+      Error::UnknownComponent(_) => "unknown-component",
+    }
+  }
+
+  /// Where in the pattern the error occurred, for errors caused by the
+  /// pattern string of a component.
+  pub fn location(&self) -> Option<&ErrorLocation> {
+    match self {
+      Error::Tokenizer(_, location)
+      | Error::Parser(_, location)
+      | Error::Canonicalize(_, location)
+      | Error::RegExp(_, location) => Some(location),
+      Error::BaseUrlRequired
+      | Error::BaseUrlWithInit
+      | Error::Url(_)
+      | Error::Generate(_)
+      | Error::Build(_)
+      | Error::UnknownComponent(_) => None,
+    }
+  }
+
+  pub(crate) fn location_mut(&mut self) -> Option<&mut ErrorLocation> {
+    match self {
+      Error::Tokenizer(_, location)
+      | Error::Parser(_, location)
+      | Error::Canonicalize(_, location)
+      | Error::RegExp(_, location) => Some(location),
+      Error::BaseUrlRequired
+      | Error::BaseUrlWithInit
+      | Error::Url(_)
+      | Error::Generate(_)
+      | Error::Build(_)
+      | Error::UnknownComponent(_) => None,
+    }
+  }
+
+  /// Attribute an error from the pattern string of a component to that
+  /// component.
+  pub(crate) fn in_component(mut self, component: ComponentKind) -> Self {
+    if let Some(location) = self.location_mut() {
+      location.component.get_or_insert(component);
+    }
+    self
+  }
+
+  /// Render the error together with the pattern it occurred in, marking the
+  /// location of the problem with carets. `pattern` must be the string the
+  /// span of the [ErrorLocation] refers to.
+  ///
+  /// ```text
+  /// error[duplicate-name]: parser error: pattern contains duplicate name id
+  ///  --> pathname
+  ///   |
+  ///   | /users/:id/:id
+  ///   |           ^^^
+  /// ```
+  pub fn render(&self, pattern: &str) -> String {
+    render(
+      "error",
+      self.code(),
+      &self.to_string(),
+      self.location(),
+      pattern,
+    )
+  }
 }
 
 impl fmt::Display for Error {
@@ -22,19 +128,150 @@ impl fmt::Display for Error {
       Error::BaseUrlWithInit => f.write_str(
         "specifying both an init object, and a separate base URL is not valid",
       ),
-      Error::Tokenizer(err, pos) => {
-        write!(f, "tokenizer error: {err} (at char {pos})")
+      Error::Tokenizer(err, location) => {
+        write!(
+          f,
+          "tokenizer error: {err} (at byte {})",
+          location.span.start
+        )
       }
-      Error::Parser(err) => write!(f, "parser error: {err}"),
+      Error::Parser(err, _) => write!(f, "parser error: {err}"),
       Error::Url(err) => err.fmt(f),
-      Error::RegExp(_) => f.write_str("regexp error"),
+      Error::Canonicalize(err, _) => write!(f, "canonicalize error: {err}"),
+      Error::RegExp(err, location) => match location.component {
+        Some(component) => {
+          write!(f, "regexp error in the {component} component: {err}")
+        }
+        None => write!(f, "regexp error: {err}"),
+      },
+      Error::Generate(err) => write!(f, "generate error: {err}"),
+      Error::Build(err) => write!(f, "build error: {err}"),
+      Error::UnknownComponent(name) => write!(f, "unknown component {name}"),
     }
   }
 }
 
 impl std::error::Error for Error {}
 
-#[derive(Debug)]
+/// The location of an error in a pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorLocation {
+  /// The component whose pattern string caused the error.
+  pub component: Option<ComponentKind>,
+  /// The byte range of the problem. For patterns parsed with
+  /// [crate::UrlPattern::parse_str], this is a range in the constructor
+  /// string. Otherwise it is a range in the pattern string of the component.
+  pub span: Range<usize>,
+}
+
+impl ErrorLocation {
+  pub(crate) fn new(span: Range<usize>) -> Self {
+    ErrorLocation {
+      component: None,
+      span,
+    }
+  }
+}
+
+/// How severe a [Diagnostic] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+  /// The pattern can not be compiled.
+  Error,
+  /// The pattern can be compiled, but probably does not do what was intended.
+  Warning,
+}
+
+impl fmt::Display for Severity {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Severity::Error => "error",
+      Severity::Warning => "warning",
+    })
+  }
+}
+
+/// A problem found in a pattern by [crate::UrlPattern::diagnose].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+  pub severity: Severity,
+  /// A stable code identifying the kind of problem. For errors, this is the
+  /// [Error::code] of the error.
+  pub code: &'static str,
+  pub message: String,
+  pub location: Option<ErrorLocation>,
+}
+
+impl Diagnostic {
+  pub(crate) fn warning(
+    code: &'static str,
+    message: impl Into<String>,
+    span: Range<usize>,
+  ) -> Self {
+    Diagnostic {
+      severity: Severity::Warning,
+      code,
+      message: message.into(),
+      location: Some(ErrorLocation::new(span)),
+    }
+  }
+
+  /// Render the diagnostic together with the pattern it was found in, like
+  /// [Error::render].
+  pub fn render(&self, pattern: &str) -> String {
+    render(
+      &self.severity.to_string(),
+      self.code,
+      &self.message,
+      self.location.as_ref(),
+      pattern,
+    )
+  }
+}
+
+impl From<Error> for Diagnostic {
+  fn from(err: Error) -> Self {
+    Diagnostic {
+      severity: Severity::Error,
+      code: err.code(),
+      message: err.to_string(),
+      location: err.location().cloned(),
+    }
+  }
+}
+
+fn render(
+  severity: &str,
+  code: &str,
+  message: &str,
+  location: Option<&ErrorLocation>,
+  pattern: &str,
+) -> String {
+  let mut output = format!("{severity}[{code}]: {message}\n");
+  let Some(location) = location else {
+    return output;
+  };
+  if let Some(component) = location.component {
+    writeln!(output, " --> {component}").unwrap();
+  }
+  let floor_char_boundary = |mut index: usize| {
+    index = index.min(pattern.len());
+    while !pattern.is_char_boundary(index) {
+      index -= 1;
+    }
+    index
+  };
+  let start = floor_char_boundary(location.span.start);
+  let end = floor_char_boundary(location.span.end).max(start);
+  let column = pattern[..start].chars().count();
+  let width = pattern[start..end].chars().count().max(1);
+  writeln!(output, "  |").unwrap();
+  writeln!(output, "  | {pattern}").unwrap();
+  writeln!(output, "  | {}{}", " ".repeat(column), "^".repeat(width)).unwrap();
+  output
+}
+
+#[derive(Debug, Clone)]
 pub enum TokenizerError {
   IncompleteEscapeCode,
   InvalidName,
@@ -55,7 +292,7 @@ impl fmt::Display for TokenizerError {
 
 impl std::error::Error for TokenizerError {}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ParserError {
   ExpectedToken(TokenType, TokenType, String),
   DuplicateName(String),
@@ -78,3 +315,75 @@ impl fmt::Display for ParserError {
 }
 
 impl std::error::Error for ParserError {}
+
+#[derive(Debug, Clone)]
+pub enum GenerateError {
+  MissingGroup(String),
+  InvalidGroupValue(String, String),
+  Unmatched(String),
+}
+
+impl fmt::Display for GenerateError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::MissingGroup(name) => {
+        write!(f, "no value was provided for group {name}")
+      }
+      Self::InvalidGroupValue(name, value) => {
+        write!(f, "value '{value}' is not valid for group {name}")
+      }
+      Self::Unmatched(url) => {
+        write!(f, "generated URL '{url}' does not match the pattern")
+      }
+    }
+  }
+}
+
+impl std::error::Error for GenerateError {}
+
+#[derive(Debug, Clone)]
+pub enum BuildError {
+  InvalidName(String),
+  InvalidRegex(String, &'static str),
+  DuplicateName(String),
+}
+
+impl fmt::Display for BuildError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::InvalidName(name) => write!(f, "'{name}' is not a valid name"),
+      Self::InvalidRegex(regex, reason) => {
+        write!(f, "invalid regex '{regex}': {reason}")
+      }
+      Self::DuplicateName(name) => {
+        write!(f, "pattern contains duplicate name {name}")
+      }
+    }
+  }
+}
+
+impl std::error::Error for BuildError {}
+
+/// A regular expression of a component that the [crate::regexp::RegExp]
+/// implementation failed to compile.
+#[derive(Debug, Clone)]
+pub struct RegExpError {
+  /// The name of the regexp group that is invalid, if the error could be
+  /// attributed to a single group.
+  pub group: Option<String>,
+  /// The error message of the regular expression engine.
+  pub message: String,
+}
+
+impl fmt::Display for RegExpError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match &self.group {
+      Some(group) => {
+        write!(f, "invalid regexp for group {group}: {}", self.message)
+      }
+      None => write!(f, "invalid regexp: {}", self.message),
+    }
+  }
+}
+
+impl std::error::Error for RegExpError {}
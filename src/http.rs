@@ -0,0 +1,169 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+//! Conversions from the types of the [http] crate into match inputs.
+//!
+//! The components of a [http::Uri] are already percent-encoded, so they are
+//! matched directly instead of being parsed into a [url::Url] first. Only the
+//! normalization that URL parsing would do for a valid HTTP URI is applied:
+//! the scheme and host are lowercased, default ports are removed, and the path
+//! is canonicalized like the pathname of a URL, so that dot segments are
+//! resolved and an empty path of a special scheme becomes `/`.
+
+use ::http::HeaderMap;
+use ::http::Request;
+use ::http::Uri;
+use ::http::header::HOST;
+use ::http::uri::Authority;
+use ::http::uri::PathAndQuery;
+use url::Url;
+
+use crate::MatchInput;
+use crate::UrlPatternMatchInput;
+use crate::canonicalize_and_process::is_special_scheme;
+use crate::canonicalize_and_process::special_scheme_default_port;
+
+impl From<&Uri> for UrlPatternMatchInput {
+  /// Convert a URI into a match input. For an origin-form URI (like
+  /// `/users/1?page=2`) the protocol and hostname are empty.
+  fn from(uri: &Uri) -> Self {
+    UrlPatternMatchInput::Parsed(match_input(
+      uri.scheme_str(),
+      uri.authority(),
+      uri.path_and_query(),
+    ))
+  }
+}
+
+impl From<&PathAndQuery> for UrlPatternMatchInput {
+  /// Convert a path and query into a match input. All components except the
+  /// pathname and search are empty.
+  fn from(path_and_query: &PathAndQuery) -> Self {
+    UrlPatternMatchInput::Parsed(match_input(None, None, Some(path_and_query)))
+  }
+}
+
+impl<B> From<&Request<B>> for UrlPatternMatchInput {
+  /// Convert a request into a match input. If the request URI is in
+  /// origin-form, the hostname and port are taken from the `Host` header
+  /// instead. An origin-form request does not carry its scheme, so the
+  /// protocol is `http` unless the request URI has one. Use
+  /// [UrlPatternMatchInput::from_request] to set the protocol of such
+  /// requests, for example for a server that terminates TLS.
+  fn from(request: &Request<B>) -> Self {
+    UrlPatternMatchInput::from_request(request, "http")
+  }
+}
+
+impl UrlPatternMatchInput {
+  /// Convert a request into a match input like its [From] impl does, with
+  /// `scheme` as the protocol if the request URI does not have a scheme.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use urlpattern::UrlPattern;
+  /// use urlpattern::UrlPatternMatchInput;
+  ///
+  ///# fn main() {
+  /// let pattern =
+  ///   <UrlPattern>::parse_str("https://example.com/users/:id", None, Default::default())
+  ///     .unwrap();
+  /// let request = http::Request::builder()
+  ///   .uri("/users/1")
+  ///   .header("Host", "example.com")
+  ///   .body(())
+  ///   .unwrap();
+  /// let input = UrlPatternMatchInput::from_request(&request, "https");
+  /// assert!(pattern.test(input).unwrap());
+  ///# }
+  /// ```
+  pub fn from_request<B>(request: &Request<B>, scheme: &str) -> Self {
+    let uri = request.uri();
+    let host = host_header(request.headers());
+    let authority = uri.authority().or(host.as_ref());
+    UrlPatternMatchInput::Parsed(match_input(
+      Some(uri.scheme_str().unwrap_or(scheme)),
+      authority,
+      uri.path_and_query(),
+    ))
+  }
+}
+
+fn host_header(headers: &HeaderMap) -> Option<Authority> {
+  let host = headers.get(HOST)?.to_str().ok()?;
+  host.parse().ok()
+}
+
+fn match_input(
+  scheme: Option<&str>,
+  authority: Option<&Authority>,
+  path_and_query: Option<&PathAndQuery>,
+) -> MatchInput {
+  let protocol = scheme.unwrap_or_default().to_ascii_lowercase();
+
+  let (username, password) = authority
+    .and_then(|authority| authority.as_str().rsplit_once('@'))
+    .map(|(userinfo, _)| match userinfo.split_once(':') {
+      Some((username, password)) => (username, password),
+      None => (userinfo, ""),
+    })
+    .unwrap_or_default();
+
+  let hostname = authority
+    .map(|authority| authority.host().to_ascii_lowercase())
+    .unwrap_or_default();
+
+  let port = authority
+    .and_then(Authority::port_u16)
+    .map(|port| port.to_string())
+    .filter(|port| special_scheme_default_port(&protocol) != Some(port))
+    .unwrap_or_default();
+
+  let pathname = canonicalize_path(
+    &protocol,
+    path_and_query.map(PathAndQuery::path).unwrap_or_default(),
+  );
+
+  let search = path_and_query
+    .and_then(PathAndQuery::query)
+    .unwrap_or_default()
+    .to_owned();
+
+  MatchInput {
+    protocol,
+    username: username.to_owned(),
+    password: password.to_owned(),
+    hostname,
+    port,
+    pathname,
+    search,
+    hash: String::new(),
+  }
+}
+
+/// Canonicalize a path the way parsing a URL does: dot segments are resolved,
+/// chars that are not allowed in a path are percent-encoded, and an empty path
+/// of a special scheme becomes `/`. An empty protocol is handled like a
+/// special scheme.
+fn canonicalize_path(protocol: &str, path: &str) -> String {
+  let special = protocol.is_empty() || is_special_scheme(protocol);
+  // Note: the path of a URI is usually canonical already, so it is only
+  // parsed if parsing could change it.
+  let canonical = !path.is_empty()
+    && path.bytes().all(|b| {
+      b.is_ascii_graphic()
+        && !matches!(b, b'.' | b'%' | b'"' | b'<' | b'>' | b'`' | b'{' | b'}')
+        && !(special && b == b'\\')
+    });
+  if canonical {
+    return path.to_owned();
+  }
+  let base = if special { "http" } else { protocol };
+  match Url::parse(&format!("{base}://dummy.test")) {
+    Ok(mut url) => {
+      url.set_path(path);
+      url::quirks::pathname(&url).to_owned()
+    }
+    Err(_) => path.to_owned(),
+  }
+}
@@ -1,5 +1,14 @@
 use crate::Error;
+use crate::parser::PartModifier;
 use crate::regexp::RegExp;
+use smallvec::SmallVec;
+use smallvec::smallvec;
+use std::sync::Arc;
+
+/// The captures of a match, in the order the groups appear in the pattern.
+/// Matches with up to four groups are stored inline, so matching does not
+/// allocate unless the regexp matcher is used.
+pub type Captures<'a> = SmallVec<[Option<&'a str>; 4]>;
 
 #[derive(Debug)]
 /// A structured representation of a URLPattern matcher, which can be used to
@@ -33,19 +42,104 @@ pub enum InnerMatcher<R: RegExp> {
     filter: Option<char>,
     allow_empty: bool,
   },
+  /// A matcher for a sequence of fixed text and wildcards, where every
+  /// wildcard captures. Segment wildcards match one or more chars that are not
+  /// `filter` (if it is set).
+  ///
+  /// # Examples
+  /// - /users/:id/posts/:post_id
+  /// - /:owner/:repo/blob/*
+  /// - :subdomain.example.:tld
+  /// - /blog/:slug?
+  /// - /files/:path+
+  /// - {www.}?example.com
+  MultiCapture {
+    parts: Vec<MatcherPart>,
+    filter: Option<char>,
+    /// Whether every wildcard can only end at a single position, so that the
+    /// parts can be matched in a single pass without backtracking. This is
+    /// the case if segment wildcards are followed by the end of the input or
+    /// by a literal that starts with `filter`, and a full wildcard is only
+    /// the last part, like in `/users/:id/posts/:post_id`.
+    linear: bool,
+  },
   /// A regexp matcher. This is a bail-out matcher for arbitrary complexity
   /// matchers.
   ///
   /// # Examples
-  /// - /foo/:id?
-  RegExp { regexp: Result<R, Error> },
+  /// - /foo/:id(\d+)
+  RegExp { regexp: Result<Arc<R>, Error> },
+}
+
+impl<R: RegExp> Clone for Matcher<R> {
+  fn clone(&self) -> Self {
+    Matcher {
+      prefix: self.prefix.clone(),
+      suffix: self.suffix.clone(),
+      inner: self.inner.clone(),
+      ignore_case: self.ignore_case,
+    }
+  }
+}
+
+impl<R: RegExp> Clone for InnerMatcher<R> {
+  fn clone(&self) -> Self {
+    match self {
+      InnerMatcher::Literal { literal } => InnerMatcher::Literal {
+        literal: literal.clone(),
+      },
+      InnerMatcher::SingleCapture {
+        filter,
+        allow_empty,
+      } => InnerMatcher::SingleCapture {
+        filter: *filter,
+        allow_empty: *allow_empty,
+      },
+      InnerMatcher::MultiCapture {
+        parts,
+        filter,
+        linear,
+      } => InnerMatcher::MultiCapture {
+        parts: parts.clone(),
+        filter: *filter,
+        linear: *linear,
+      },
+      InnerMatcher::RegExp { regexp } => InnerMatcher::RegExp {
+        regexp: regexp.clone(),
+      },
+    }
+  }
+}
+
+/// A part of a [InnerMatcher::MultiCapture] matcher.
+#[derive(Debug, Clone)]
+pub enum MatcherPart {
+  /// Fixed text that must be present.
+  Literal(String),
+  /// A lazy capture of one or more chars, which can not contain the filter of
+  /// the matcher.
+  SegmentWildcard,
+  /// A greedy capture of zero or more chars.
+  FullWildcard,
+  /// Fixed text that is matched if it is present, like `{www.}?`.
+  OptionalLiteral(String),
+  /// A segment wildcard with a modifier, like `/:slug?` or `/:path+`. The
+  /// prefix and suffix are only matched together with the wildcard, and a
+  /// repeated wildcard captures all repetitions including the prefixes and
+  /// suffixes between them.
+  SegmentGroup {
+    prefix: String,
+    suffix: String,
+    modifier: PartModifier,
+  },
+  /// The rest of the input in [crate::MatchMode::Prefix], which is captured
+  /// like a trailing full wildcard. It is either empty or starts with the
+  /// filter of the matcher, and it can not be empty if it is `required`.
+  Remainder { required: bool },
 }
 
 impl<R: RegExp> Matcher<R> {
-  pub fn matches<'a>(
-    &self,
-    mut input: &'a str,
-  ) -> Option<Vec<Option<&'a str>>> {
+  pub fn matches<'a>(&self, mut input: &'a str) -> Option<Captures<'a>> {
     let prefix_len = self.prefix.len();
     let suffix_len = self.suffix.len();
     let input_len = input.len();
@@ -55,10 +149,10 @@ impl<R: RegExp> Matcher<R> {
       if input_len < prefix_len + suffix_len {
         return None;
       }
-      if !input.starts_with(&self.prefix) {
+      if !starts_with(input, &self.prefix, self.ignore_case) {
         return None;
       }
-      if !input.ends_with(&self.suffix) {
+      if !ends_with(input, &self.suffix, self.ignore_case) {
         return None;
       }
       input = &input[prefix_len..input_len - suffix_len];
@@ -67,9 +161,9 @@ impl<R: RegExp> Matcher<R> {
     match &self.inner {
       InnerMatcher::Literal { literal } => {
         if self.ignore_case {
-          (input.to_lowercase() == literal.to_lowercase()).then(Vec::new)
+          input.eq_ignore_ascii_case(literal).then(Captures::new)
         } else {
-          (input == literal).then(Vec::new)
+          (input == literal).then(Captures::new)
         }
       }
       InnerMatcher::SingleCapture {
@@ -91,11 +185,387 @@ impl<R: RegExp> Matcher<R> {
             return None;
           }
         }
-        Some(vec![Some(input)])
+        Some(smallvec![Some(input)])
+      }
+      InnerMatcher::MultiCapture {
+        parts,
+        filter,
+        linear: true,
+      } => match_linear(parts, *filter, self.ignore_case, input),
+      InnerMatcher::MultiCapture {
+        parts,
+        filter,
+        linear: false,
+      } => {
+        let mut matcher = MultiCaptureMatcher {
+          parts,
+          filter: *filter,
+          ignore_case: self.ignore_case,
+          input,
+          captures: Captures::new(),
+          failed: FailedStates::default(),
+        };
+        matcher.matches(0, 0).then_some(matcher.captures)
       }
       InnerMatcher::RegExp { regexp, .. } => {
-        regexp.as_ref().ok()?.matches(input)
+        regexp.as_ref().ok()?.matches(input).map(Captures::from_vec)
+      }
+    }
+  }
+}
+
+/// Returns whether the parts of a [InnerMatcher::MultiCapture] matcher can be
+/// matched without backtracking. See [InnerMatcher::MultiCapture::linear].
+pub(crate) fn is_linear(parts: &[MatcherPart], filter: Option<char>) -> bool {
+  parts.iter().enumerate().all(|(i, part)| {
+    let next = parts.get(i + 1);
+    match part {
+      MatcherPart::Literal(_) => true,
+      MatcherPart::SegmentWildcard => match (filter, next) {
+        (Some(_), None) => true,
+        (Some(filter), Some(MatcherPart::Literal(literal))) => {
+          literal.starts_with(filter)
+        }
+        (Some(_), Some(MatcherPart::Remainder { .. })) => true,
+        _ => false,
+      },
+      MatcherPart::FullWildcard | MatcherPart::Remainder { .. } => {
+        next.is_none()
+      }
+      MatcherPart::OptionalLiteral(_) | MatcherPart::SegmentGroup { .. } => {
+        false
+      }
+    }
+  })
+}
+
+/// Match the parts of a linear [InnerMatcher::MultiCapture] matcher in a
+/// single pass. A segment wildcard ends at the next `filter`, because it can
+/// not contain it and the literal after it starts with it.
+fn match_linear<'a>(
+  parts: &[MatcherPart],
+  filter: Option<char>,
+  ignore_case: bool,
+  input: &'a str,
+) -> Option<Captures<'a>> {
+  let mut captures = Captures::new();
+  let mut rest = input;
+  for part in parts {
+    match part {
+      MatcherPart::Literal(literal) => {
+        if !starts_with(rest, literal, ignore_case) {
+          return None;
+        }
+        rest = &rest[literal.len()..];
+      }
+      MatcherPart::SegmentWildcard => {
+        let len = filter
+          .and_then(|filter| rest.find(filter))
+          .unwrap_or(rest.len());
+        if len == 0 {
+          return None;
+        }
+        captures.push(Some(&rest[..len]));
+        rest = &rest[len..];
+      }
+      MatcherPart::FullWildcard => {
+        captures.push(Some(rest));
+        rest = "";
+      }
+      MatcherPart::Remainder { required } => {
+        if !remainder_matches(rest, filter, *required) {
+          return None;
+        }
+        captures.push(Some(rest));
+        rest = "";
+      }
+      MatcherPart::OptionalLiteral(_) | MatcherPart::SegmentGroup { .. } => {
+        unreachable!("the parts of a linear matcher can not be optional")
       }
     }
   }
+  rest.is_empty().then_some(captures)
+}
+
+/// Whether `rest` is a valid remainder for a [MatcherPart::Remainder].
+fn remainder_matches(rest: &str, filter: Option<char>, required: bool) -> bool {
+  match filter {
+    Some(filter) if !rest.is_empty() => rest.starts_with(filter),
+    _ => !required,
+  }
+}
+
+/// The states of a [MultiCaptureMatcher] that are known to fail, as a bitset
+/// that is only allocated once a state fails.
+#[derive(Default)]
+struct FailedStates {
+  bits: Vec<u64>,
+}
+
+impl FailedStates {
+  fn contains(&self, state: usize) -> bool {
+    self
+      .bits
+      .get(state / 64)
+      .is_some_and(|word| word & (1 << (state % 64)) != 0)
+  }
+
+  /// Add a state, allocating the bitset for `len` states if necessary.
+  fn insert(&mut self, state: usize, len: usize) {
+    if self.bits.is_empty() {
+      self.bits = vec![0; len.div_ceil(64)];
+    }
+    self.bits[state / 64] |= 1 << (state % 64);
+  }
+}
+
+/// A backtracking matcher for [InnerMatcher::MultiCapture]. Alternatives are
+/// tried in the same order as the regular expression generated for the parts
+/// would try them (segment wildcards are lazy, full wildcards, optional groups
+/// and repetitions are greedy), so the captures are identical to the ones of
+/// the regular expression.
+struct MultiCaptureMatcher<'a, 'p> {
+  parts: &'p [MatcherPart],
+  filter: Option<char>,
+  ignore_case: bool,
+  input: &'a str,
+  captures: Captures<'a>,
+  /// Whether matching the parts from a part index at an input position is
+  /// known to fail, indexed by `part_index * (input.len() + 1) + pos`, and
+  /// followed by the states of repetitions in the same layout. This bounds
+  /// the amount of backtracking for patterns with many wildcards.
+  failed: FailedStates,
+}
+
+impl<'a> MultiCaptureMatcher<'a, '_> {
+  /// The number of states of matching the parts, or of repetitions.
+  fn state_count(&self) -> usize {
+    self.parts.len() * (self.input.len() + 1)
+  }
+
+  fn matches(&mut self, part_index: usize, pos: usize) -> bool {
+    let Some(part) = self.parts.get(part_index) else {
+      return pos == self.input.len();
+    };
+    let state = part_index * (self.input.len() + 1) + pos;
+    if self.failed.contains(state) {
+      return false;
+    }
+
+    let matched = match part {
+      MatcherPart::Literal(literal) => self
+        .literal_end(literal, pos)
+        .is_some_and(|end| self.matches(part_index + 1, end)),
+      MatcherPart::OptionalLiteral(literal) => {
+        self
+          .literal_end(literal, pos)
+          .is_some_and(|end| self.matches(part_index + 1, end))
+          || self.matches(part_index + 1, pos)
+      }
+      MatcherPart::SegmentWildcard => {
+        segment_ends(self.input, pos, self.filter)
+          .any(|end| self.capture(part_index, end, Some(&self.input[pos..end])))
+      }
+      MatcherPart::FullWildcard => {
+        let rest = &self.input[pos..];
+        rest
+          .char_indices()
+          .map(|(i, _)| pos + i)
+          .chain([self.input.len()])
+          .rev()
+          .any(|end| self.capture(part_index, end, Some(&self.input[pos..end])))
+      }
+      MatcherPart::Remainder { required } => {
+        let rest = &self.input[pos..];
+        remainder_matches(rest, self.filter, *required)
+          && self.capture(part_index, self.input.len(), Some(rest))
+      }
+      MatcherPart::SegmentGroup {
+        prefix,
+        suffix,
+        modifier,
+      } => {
+        self.segment_group_matches(part_index, prefix, suffix, *modifier, pos)
+      }
+    };
+    // Note: a failing literal is cheap to match again, so it is not recorded
+    // to avoid allocating the bitset for inputs that simply do not match.
+    if !matched && !matches!(part, MatcherPart::Literal(_)) {
+      self.failed.insert(state, 2 * self.state_count());
+    }
+    matched
+  }
+
+  /// Try to match the rest of the parts after the wildcard at `part_index`
+  /// matched up to `end` with the given capture.
+  fn capture(
+    &mut self,
+    part_index: usize,
+    end: usize,
+    capture: Option<&'a str>,
+  ) -> bool {
+    self.captures.push(capture);
+    if self.matches(part_index + 1, end) {
+      return true;
+    }
+    self.captures.pop();
+    false
+  }
+
+  /// The end of `literal` if the input contains it at `pos`.
+  fn literal_end(&self, literal: &str, pos: usize) -> Option<usize> {
+    starts_with(&self.input[pos..], literal, self.ignore_case)
+      .then_some(pos + literal.len())
+  }
+
+  /// Try all ways in which the segment wildcard group at `part_index` can
+  /// match at `pos`, in the order the regular expression would try them, and
+  /// match the rest of the parts after each of them.
+  fn segment_group_matches(
+    &mut self,
+    part_index: usize,
+    prefix: &str,
+    suffix: &str,
+    modifier: PartModifier,
+    pos: usize,
+  ) -> bool {
+    let input = self.input;
+    let filter = self.filter;
+    if let Some(start) = self.literal_end(prefix, pos) {
+      let matched = match modifier {
+        PartModifier::None | PartModifier::Optional => {
+          segment_ends(input, start, filter).any(|end| {
+            self.segment_group_end_matches(part_index, suffix, start, end)
+          })
+        }
+        // `((?:segment)*)` can repeat zero times, which captures an empty
+        // string.
+        PartModifier::ZeroOrMore if prefix.is_empty() && suffix.is_empty() => {
+          self.repetition_matches(part_index, prefix, suffix, start, start)
+        }
+        PartModifier::ZeroOrMore | PartModifier::OneOrMore => {
+          segment_ends(input, start, filter).any(|end| {
+            self.repetition_matches(part_index, prefix, suffix, start, end)
+          })
+        }
+      };
+      if matched {
+        return true;
+      }
+    }
+
+    // `(?:prefix(segment)suffix)?` can be skipped entirely, which does not
+    // capture anything.
+    let skippable = match modifier {
+      PartModifier::Optional => true,
+      PartModifier::ZeroOrMore => !prefix.is_empty() || !suffix.is_empty(),
+      PartModifier::None | PartModifier::OneOrMore => false,
+    };
+    skippable && self.capture(part_index, pos, None)
+  }
+
+  /// Try to end the segment wildcard group at `part_index`, whose segments
+  /// start at `start` and end at `end`, and match the rest of the parts.
+  fn segment_group_end_matches(
+    &mut self,
+    part_index: usize,
+    suffix: &str,
+    start: usize,
+    end: usize,
+  ) -> bool {
+    let input = self.input;
+    self.literal_end(suffix, end).is_some_and(|match_end| {
+      self.capture(part_index, match_end, Some(&input[start..end]))
+    })
+  }
+
+  /// Try to continue a repetition of segment wildcards, each separated by the
+  /// suffix and prefix, after a segment wildcard that ended at `pos`. Another
+  /// repetition is tried before ending at `pos`, because the repetition is
+  /// greedy.
+  ///
+  /// Whether the rest of the parts match does not depend on where the
+  /// repetition started, so a position that failed once fails for all
+  /// starts.
+  fn repetition_matches(
+    &mut self,
+    part_index: usize,
+    prefix: &str,
+    suffix: &str,
+    start: usize,
+    pos: usize,
+  ) -> bool {
+    let state = self.state_count() + part_index * (self.input.len() + 1) + pos;
+    if self.failed.contains(state) {
+      return false;
+    }
+    let input = self.input;
+    let filter = self.filter;
+    let next = self
+      .literal_end(suffix, pos)
+      .and_then(|end| self.literal_end(prefix, end));
+    let matched = next.is_some_and(|next| {
+      segment_ends(input, next, filter).any(|end| {
+        self.repetition_matches(part_index, prefix, suffix, start, end)
+      })
+    }) || self
+      .segment_group_end_matches(part_index, suffix, start, pos);
+    if !matched {
+      self.failed.insert(state, 2 * self.state_count());
+    }
+    matched
+  }
+}
+
+/// The possible ends of a segment wildcard that starts at `pos`, shortest
+/// first. A segment wildcard matches at least one char, and can not contain
+/// `filter`.
+fn segment_ends(
+  input: &str,
+  pos: usize,
+  filter: Option<char>,
+) -> impl Iterator<Item = usize> + '_ {
+  let rest = &input[pos..];
+  let len = match filter {
+    Some(filter) => rest.find(filter).unwrap_or(rest.len()),
+    None => rest.len(),
+  };
+  rest[..len]
+    .char_indices()
+    .map(move |(i, c)| pos + i + c.len_utf8())
+}
+
+/// Whether the fixed text of a matcher can be compared ignoring the case of
+/// ASCII letters only, and still match the same inputs as the case
+/// insensitive regexp of the component. The regexp folds case by unicode
+/// rules, so this is not the case for non-ASCII chars, nor for `k` and `s`,
+/// which also match the Kelvin sign (`K`) and the long s (`ſ`).
+pub(crate) fn folds_like_ascii(text: &str) -> bool {
+  text
+    .bytes()
+    .all(|b| b.is_ascii() && !matches!(b.to_ascii_lowercase(), b'k' | b's'))
+}
+
+/// Whether `input` starts with `prefix`. If `ignore_case` is set, ASCII
+/// letters are compared case insensitively. All other bytes must be equal, so
+/// the end of the prefix is a char boundary of the input if this returns true.
+fn starts_with(input: &str, prefix: &str, ignore_case: bool) -> bool {
+  if ignore_case {
+    input.len() >= prefix.len()
+      && input.as_bytes()[..prefix.len()]
+        .eq_ignore_ascii_case(prefix.as_bytes())
+  } else {
+    input.starts_with(prefix)
+  }
+}
+
+/// Whether `input` ends with `suffix`. See [starts_with].
+fn ends_with(input: &str, suffix: &str, ignore_case: bool) -> bool {
+  if ignore_case {
+    input.len() >= suffix.len()
+      && input.as_bytes()[input.len() - suffix.len()..]
+        .eq_ignore_ascii_case(suffix.as_bytes())
+  } else {
+    input.ends_with(suffix)
+  }
 }
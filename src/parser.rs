@@ -1,19 +1,23 @@
 // Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
 
 use crate::Error;
+use crate::error::Diagnostic;
+use crate::error::ErrorLocation;
 use crate::error::ParserError;
+use crate::regexp::RegExpFlags;
 use crate::tokenizer::Token;
 use crate::tokenizer::TokenType;
 
 use serde::Deserialize;
 use serde::Serialize;
+use std::ops::Range;
 
 // Ref: https://wicg.github.io/urlpattern/#full-wildcard-regexp-value
 pub const FULL_WILDCARD_REGEXP_VALUE: &str = ".*";
 
 /// The regexp syntax that should be used.
 #[derive(
-  Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize,
+  Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize,
 )]
 pub enum RegexSyntax {
   /// Compile regexes to rust-regex syntax. This is the default.
@@ -27,6 +31,27 @@ pub enum RegexSyntax {
   EcmaScript,
 }
 
+/// How the pathname of a pattern is matched against the pathname of an input.
+#[derive(
+  Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub enum MatchMode {
+  /// The pattern must match the whole pathname. This is the default.
+  #[default]
+  Full,
+  /// The pattern only has to match the start of the pathname, up to a `/` or
+  /// the end of the pathname, like the mount point of a nested router. The
+  /// rest of the pathname is the remainder of the match, see
+  /// [crate::UrlPattern::into_prefix] and [crate::UrlPattern::exec_prefix].
+  Prefix,
+}
+
+impl MatchMode {
+  pub(crate) fn is_full(&self) -> bool {
+    *self == MatchMode::Full
+  }
+}
+
 // Ref: https://wicg.github.io/urlpattern/#options-header
 #[derive(Debug, Clone)]
 pub struct Options {
@@ -34,6 +59,7 @@ pub struct Options {
   pub prefix_code_point: String, // TODO: It must contain one ASCII code point or the empty string. maybe Option<char>?
   pub regex_syntax: RegexSyntax,
   pub ignore_case: bool,
+  pub match_mode: MatchMode,
 }
 
 impl std::default::Default for Options {
@@ -45,6 +71,7 @@ impl std::default::Default for Options {
       prefix_code_point: String::new(),
       regex_syntax: RegexSyntax::Rust,
       ignore_case: false,
+      match_mode: MatchMode::Full,
     }
   }
 }
@@ -58,6 +85,7 @@ impl Options {
       prefix_code_point: String::new(),
       regex_syntax: RegexSyntax::Rust,
       ignore_case: false,
+      match_mode: MatchMode::Full,
     }
   }
 
@@ -69,6 +97,15 @@ impl Options {
       prefix_code_point: String::from("/"),
       regex_syntax: RegexSyntax::Rust,
       ignore_case: false,
+      match_mode: MatchMode::Full,
+    }
+  }
+
+  /// The flags of the regular expressions generated with these options.
+  pub fn regexp_flags(&self) -> RegExpFlags {
+    RegExpFlags {
+      ignore_case: self.ignore_case,
+      unicode: true,
     }
   }
 
@@ -122,7 +159,8 @@ impl Options {
 }
 
 // Ref: https://wicg.github.io/urlpattern/#part-type
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum PartType {
   FixedText,
   Regexp,
@@ -131,7 +169,8 @@ pub enum PartType {
 }
 
 // Ref: https://wicg.github.io/urlpattern/#part-modifier
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum PartModifier {
   None,
   Optional,
@@ -152,7 +191,9 @@ impl std::fmt::Display for PartModifier {
 }
 
 // Ref: https://wicg.github.io/urlpattern/#part
-#[derive(Debug)]
+/// A part of a parsed pattern string. The value, prefix and suffix of a part
+/// are canonicalized for the component the pattern string is for.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
 pub struct Part {
   pub kind: PartType,
   pub value: String,
@@ -163,7 +204,11 @@ pub struct Part {
 }
 
 impl Part {
-  fn new(kind: PartType, value: String, modifier: PartModifier) -> Self {
+  pub(crate) fn new(
+    kind: PartType,
+    value: String,
+    modifier: PartModifier,
+  ) -> Self {
     Part {
       kind,
       value,
@@ -185,8 +230,13 @@ where
   segment_wildcard_regexp: String,
   part_list: Vec<Part>,
   pending_fixed_value: String,
+  /// The span of the tokens that make up the pending fixed value.
+  pending_fixed_span: Range<usize>,
   index: usize,
   next_numeric_name: usize,
+  /// The errors and warnings found so far, if the parser recovers from errors
+  /// instead of returning them.
+  diagnostics: Option<Vec<Diagnostic>>,
 }
 
 impl<'a, F> PatternParser<'a, F>
@@ -227,14 +277,79 @@ where
       .or_else(|| self.try_consume_token(TokenType::Asterisk))
   }
 
+  /// The span from `start` to the end of the last consumed token.
+  #[inline]
+  fn span_from(&self, start: usize) -> Range<usize> {
+    start..self.token_list[self.index - 1].end
+  }
+
+  /// Run the encoding callback on fixed text from `span` of the input.
+  fn encode(&self, value: &str, span: Range<usize>) -> Result<String, Error> {
+    (self.encoding_callback)(value).map_err(|err| match err {
+      Error::Url(err) => Error::Canonicalize(err, ErrorLocation::new(span)),
+      err => err,
+    })
+  }
+
+  /// Record an error and continue parsing if diagnostics are being collected,
+  /// or return it otherwise.
+  fn recover(&mut self, err: Error) -> Result<(), Error> {
+    match &mut self.diagnostics {
+      Some(diagnostics) => {
+        diagnostics.push(err.into());
+        Ok(())
+      }
+      None => Err(err),
+    }
+  }
+
+  fn add_warning(
+    &mut self,
+    code: &'static str,
+    message: &str,
+    span: Range<usize>,
+  ) {
+    if let Some(diagnostics) = &mut self.diagnostics {
+      diagnostics.push(Diagnostic::warning(code, message, span));
+    }
+  }
+
+  /// Like [Self::encode], but when recovering from errors, the value is kept
+  /// as is if it can not be encoded.
+  fn encode_or_recover(
+    &mut self,
+    value: &str,
+    span: Range<usize>,
+  ) -> Result<String, Error> {
+    match self.encode(value, span) {
+      Ok(encoded) => Ok(encoded),
+      Err(err) => {
+        self.recover(err)?;
+        Ok(value.to_owned())
+      }
+    }
+  }
+
+  fn push_pending_fixed_value(&mut self, value: &str, span: Range<usize>) {
+    if self.pending_fixed_value.is_empty() {
+      self.pending_fixed_span = span;
+    } else {
+      self.pending_fixed_span.end = span.end;
+    }
+    self.pending_fixed_value.push_str(value);
+  }
+
   // Ref: https://wicg.github.io/urlpattern/#maybe-add-a-part-from-the-pending-fixed-value
   #[inline]
   fn maybe_add_part_from_pending_fixed_value(&mut self) -> Result<(), Error> {
     if self.pending_fixed_value.is_empty() {
       return Ok(());
     }
-    let encoded_value = (self.encoding_callback)(&self.pending_fixed_value)?;
-    self.pending_fixed_value = String::new();
+    let pending_fixed_value = std::mem::take(&mut self.pending_fixed_value);
+    let encoded_value = self.encode_or_recover(
+      &pending_fixed_value,
+      self.pending_fixed_span.clone(),
+    )?;
     self.part_list.push(Part::new(
       PartType::FixedText,
       encoded_value,
@@ -252,6 +367,7 @@ where
     regexp_or_wildcard_token: Option<Token>,
     suffix: &str,
     modifier_token: Option<Token>,
+    span: Range<usize>,
   ) -> Result<(), Error> {
     let mut modifier = PartModifier::None;
     if let Some(modifier_token) = modifier_token {
@@ -266,7 +382,7 @@ where
       && regexp_or_wildcard_token.is_none()
       && modifier == PartModifier::None
     {
-      self.pending_fixed_value.push_str(prefix);
+      self.push_pending_fixed_value(prefix, span);
       return Ok(());
     }
     self.maybe_add_part_from_pending_fixed_value()?;
@@ -275,7 +391,7 @@ where
       if prefix.is_empty() {
         return Ok(());
       }
-      let encoded_value = (self.encoding_callback)(prefix)?;
+      let encoded_value = self.encode_or_recover(prefix, span)?;
       self.part_list.push(Part::new(
         PartType::FixedText,
         encoded_value,
@@ -305,17 +421,23 @@ where
     }
 
     let mut name = String::new();
-    if let Some(name_token) = name_token {
+    if let Some(name_token) = &name_token {
       name = name_token.value.to_owned();
     } else if regexp_or_wildcard_token.is_some() {
       name = self.next_numeric_name.to_string();
       self.next_numeric_name += 1;
     }
     if self.is_duplicate_name(&name) {
-      return Err(Error::Parser(ParserError::DuplicateName(name)));
+      // Note: unwrap is safe, because a part with a name has a name token or a
+      // regexp or wildcard token.
+      let token = name_token.or(regexp_or_wildcard_token).unwrap();
+      self.recover(Error::Parser(
+        ParserError::DuplicateName(name.clone()),
+        ErrorLocation::new(token.index..token.end),
+      ))?;
     }
-    let encoded_prefix = (self.encoding_callback)(prefix)?;
-    let encoded_suffix = (self.encoding_callback)(suffix)?;
+    let encoded_prefix = self.encode_or_recover(prefix, span.clone())?;
+    let encoded_suffix = self.encode_or_recover(suffix, span)?;
     self.part_list.push(Part {
       kind,
       value: regexp_value,
@@ -355,13 +477,23 @@ where
     &mut self,
     kind: TokenType,
   ) -> Result<Token, Error> {
-    self.try_consume_token(kind.clone()).ok_or_else(|| {
-      Error::Parser(ParserError::ExpectedToken(
-        kind,
-        self.token_list[self.index].kind.clone(),
-        self.token_list[self.index].value.to_owned(),
-      ))
-    })
+    match self.try_consume_token(kind.clone()) {
+      Some(token) => Ok(token),
+      None => Err(self.unexpected_token(kind)),
+    }
+  }
+
+  /// The error for when the next token is not of the expected kind.
+  fn unexpected_token(&self, expected: TokenType) -> Error {
+    let token = &self.token_list[self.index];
+    Error::Parser(
+      ParserError::ExpectedToken(
+        expected,
+        token.kind.clone(),
+        token.value.to_owned(),
+      ),
+      ErrorLocation::new(token.index..token.end),
+    )
   }
 }
 
@@ -378,39 +510,82 @@ where
     input,
     crate::tokenizer::TokenizePolicy::Strict,
   )?;
+  parse_token_list(token_list, options, encoding_callback, None)
+    .map(|(part_list, _)| part_list)
+}
+
+/// Parse a pattern string like [parse_pattern_string], but recover from all
+/// errors, returning a best-effort part list together with every error and
+/// warning found in the input.
+pub(crate) fn parse_pattern_string_with_diagnostics<F>(
+  input: &str,
+  options: &Options,
+  encoding_callback: F,
+) -> (Vec<Part>, Vec<Diagnostic>)
+where
+  F: Fn(&str) -> Result<String, Error>,
+{
+  let (token_list, diagnostics) =
+    crate::tokenizer::tokenize_with_diagnostics(input);
+  // Note: unwrap is safe, because the parser recovers from every error when
+  // it collects diagnostics.
+  let (part_list, diagnostics) =
+    parse_token_list(token_list, options, encoding_callback, Some(diagnostics))
+      .unwrap();
+  (part_list, diagnostics.unwrap_or_default())
+}
 
+fn parse_token_list<F>(
+  token_list: Vec<Token>,
+  options: &Options,
+  encoding_callback: F,
+  diagnostics: Option<Vec<Diagnostic>>,
+) -> Result<(Vec<Part>, Option<Vec<Diagnostic>>), Error>
+where
+  F: Fn(&str) -> Result<String, Error>,
+{
   let mut parser = PatternParser {
     token_list,
     encoding_callback,
     segment_wildcard_regexp: options.generate_segment_wildcard_regexp(),
     part_list: vec![],
     pending_fixed_value: String::new(),
+    pending_fixed_span: 0..0,
     index: 0,
     next_numeric_name: 0,
+    diagnostics,
   };
 
   while parser.index < parser.token_list.len() {
+    let start = parser.token_list[parser.index].index;
     let char_token = parser.try_consume_token(TokenType::Char);
     let mut name_token = parser.try_consume_token(TokenType::Name);
     let mut regexp_or_wildcard_token =
       parser.try_consume_regexp_or_wildcard_token(name_token.is_none());
     if name_token.is_some() || regexp_or_wildcard_token.is_some() {
       let mut prefix = String::new();
+      let mut part_start = start;
       if let Some(char_token) = char_token {
         char_token.value.clone_into(&mut prefix);
-      }
-      if !prefix.is_empty() && prefix != options.prefix_code_point {
-        parser.pending_fixed_value.push_str(&prefix);
-        prefix = String::new();
+        if prefix != options.prefix_code_point {
+          parser.push_pending_fixed_value(
+            &prefix,
+            char_token.index..char_token.end,
+          );
+          prefix = String::new();
+          part_start = char_token.end;
+        }
       }
       parser.maybe_add_part_from_pending_fixed_value()?;
       let modifier_token = parser.try_consume_modifier_token();
+      let span = parser.span_from(part_start);
       parser.add_part(
         &prefix,
         name_token,
         regexp_or_wildcard_token,
         "",
         modifier_token,
+        span,
       )?;
       continue;
     }
@@ -419,7 +594,10 @@ where
       fixed_token = parser.try_consume_token(TokenType::EscapedChar);
     }
     if let Some(fixed_token) = fixed_token {
-      parser.pending_fixed_value.push_str(fixed_token.value);
+      parser.push_pending_fixed_value(
+        fixed_token.value,
+        fixed_token.index..fixed_token.end,
+      );
       continue;
     }
     let open_token = parser.try_consume_token(TokenType::Open);
@@ -429,20 +607,45 @@ where
       regexp_or_wildcard_token =
         parser.try_consume_regexp_or_wildcard_token(name_token.is_none());
       let suffix = parser.consume_text();
-      parser.consume_required_token(TokenType::Close)?;
+      if let Err(err) = parser.consume_required_token(TokenType::Close) {
+        parser.recover(err)?;
+      }
       let modifier_token = parser.try_consume_modifier_token();
+      let span = parser.span_from(start);
+      if prefix.is_empty()
+        && name_token.is_none()
+        && regexp_or_wildcard_token.is_none()
+        && suffix.is_empty()
+      {
+        parser.add_warning("empty-group", "group is empty", span.clone());
+      }
       parser.add_part(
         &prefix,
         name_token,
         regexp_or_wildcard_token,
         &suffix,
         modifier_token,
+        span,
       )?;
       continue;
     }
+    if parser.diagnostics.is_some() {
+      // When recovering from errors, any unexpected token is treated as fixed
+      // text. Invalid tokens were already reported by the tokenizer.
+      let token = parser.token_list[parser.index].clone();
+      if token.kind != TokenType::End {
+        if token.kind != TokenType::InvalidChar {
+          let err = parser.unexpected_token(TokenType::End);
+          parser.recover(err)?;
+        }
+        parser.index += 1;
+        parser.push_pending_fixed_value(token.value, token.index..token.end);
+        continue;
+      }
+    }
     parser.maybe_add_part_from_pending_fixed_value()?;
     parser.consume_required_token(TokenType::End)?;
   }
 
-  Ok(parser.part_list)
+  Ok((parser.part_list, parser.diagnostics))
 }
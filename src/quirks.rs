@@ -7,10 +7,12 @@ use std::borrow::Cow;
 use url::Url;
 
 pub use crate::Error;
+pub use crate::MatchInput;
 use crate::UrlPatternOptions;
 pub use crate::component::Component;
 use crate::parser::RegexSyntax;
 use crate::regexp::RegExp;
+use crate::regexp::RegExpFlags;
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct UrlPatternInit {
@@ -109,10 +111,53 @@ impl<R: RegExp> From<Component<R>> for UrlPatternComponent {
       .regexp
       .map(|r| r.pattern_string().to_owned())
       .unwrap_or_default();
+    let crate::matcher::Matcher {
+      prefix,
+      suffix,
+      inner,
+      ..
+    } = component.matcher;
+    let matcher = match inner {
+      crate::matcher::InnerMatcher::Literal { literal } => Matcher {
+        prefix,
+        suffix,
+        inner: InnerMatcher::Literal { literal },
+      },
+      crate::matcher::InnerMatcher::SingleCapture {
+        filter,
+        allow_empty,
+      } => Matcher {
+        prefix,
+        suffix,
+        inner: InnerMatcher::SingleCapture {
+          filter,
+          allow_empty,
+        },
+      },
+      // Multi capture matchers are a fast path of this crate, so they are
+      // exposed as a matcher for the regexp of the whole component, which
+      // includes the prefix and suffix.
+      crate::matcher::InnerMatcher::MultiCapture { .. } => Matcher {
+        prefix: String::new(),
+        suffix: String::new(),
+        inner: InnerMatcher::RegExp {
+          regexp: regexp_string.clone(),
+        },
+      },
+      crate::matcher::InnerMatcher::RegExp { regexp } => Matcher {
+        prefix,
+        suffix,
+        inner: InnerMatcher::RegExp {
+          regexp: regexp
+            .map(|r| r.pattern_string().to_owned())
+            .unwrap_or_default(),
+        },
+      },
+    };
     Self {
       pattern_string: component.pattern_string,
       regexp_string,
-      matcher: component.matcher.into(),
+      matcher,
       group_name_list: component.group_name_list,
     }
   }
@@ -143,59 +188,30 @@ pub enum InnerMatcher {
   },
 }
 
-impl<R: RegExp> From<crate::matcher::Matcher<R>> for Matcher {
-  fn from(matcher: crate::matcher::Matcher<R>) -> Self {
-    Self {
-      prefix: matcher.prefix,
-      suffix: matcher.suffix,
-      inner: matcher.inner.into(),
-    }
-  }
-}
-
-impl<R: RegExp> From<crate::matcher::InnerMatcher<R>> for InnerMatcher {
-  fn from(inner: crate::matcher::InnerMatcher<R>) -> Self {
-    match inner {
-      crate::matcher::InnerMatcher::Literal { literal } => {
-        Self::Literal { literal }
-      }
-      crate::matcher::InnerMatcher::SingleCapture {
-        filter,
-        allow_empty,
-      } => Self::SingleCapture {
-        filter,
-        allow_empty,
-      },
-      crate::matcher::InnerMatcher::RegExp { regexp } => Self::RegExp {
-        regexp: regexp
-          .map(|r| r.pattern_string().to_owned())
-          .unwrap_or_default(),
-      },
-    }
-  }
-}
-
-pub struct EcmaRegexp(String, String);
+pub struct EcmaRegexp(String, RegExpFlags);
 
 impl RegExp for EcmaRegexp {
+  type Error = regex::Error;
+
   fn syntax() -> RegexSyntax {
     RegexSyntax::EcmaScript
   }
 
-  fn parse(pattern: &str, flags: &str, force_eval: bool) -> Result<Self, ()> {
+  fn parse(
+    pattern: &str,
+    flags: RegExpFlags,
+    force_eval: bool,
+  ) -> Result<Self, Self::Error> {
     if force_eval {
-      let regexp = regex::Regex::parse(pattern, flags, false);
-      match regexp {
-        Ok(r) => Ok(EcmaRegexp(r.to_string(), flags.to_string())),
-        _ => Err(()),
-      }
+      let regexp = regex::Regex::parse(pattern, flags, false)?;
+      Ok(EcmaRegexp(regexp.to_string(), flags))
     } else {
-      Ok(EcmaRegexp(pattern.to_string(), flags.to_string()))
+      Ok(EcmaRegexp(pattern.to_string(), flags))
     }
   }
 
   fn matches<'a>(&self, text: &'a str) -> Option<Vec<Option<&'a str>>> {
-    let regexp = regex::Regex::parse(&self.0, &self.1, false).ok()?;
+    let regexp = regex::Regex::parse(&self.0, self.1, false).ok()?;
     regexp.matches(text)
   }
 
@@ -235,6 +251,40 @@ pub fn parse_pattern_as_lib<R: RegExp>(
 
 pub type Inputs<'a> = (StringOrInit<'a>, Option<String>);
 
+/// Compare a component of two patterns by specificity, returning `-1`, `0`, or
+/// `1` like `URLPattern.compareComponent()`. The component is given by its
+/// name, for example `pathname`.
+pub fn compare_component(
+  component: &str,
+  left: Inputs,
+  right: Inputs,
+) -> Result<i8, Error> {
+  use crate::component::ComponentKind;
+  let kind = [
+    ComponentKind::Protocol,
+    ComponentKind::Username,
+    ComponentKind::Password,
+    ComponentKind::Hostname,
+    ComponentKind::Port,
+    ComponentKind::Pathname,
+    ComponentKind::Search,
+    ComponentKind::Hash,
+  ]
+  .into_iter()
+  .find(|kind| kind.to_string() == component)
+  .ok_or_else(|| Error::UnknownComponent(component.to_owned()))?;
+  let parse = |(input, base_url): Inputs| {
+    let init = process_construct_pattern_input(input, base_url.as_deref())?;
+    crate::UrlPattern::<EcmaRegexp>::parse_internal(
+      init,
+      false,
+      Default::default(),
+    )
+  };
+  let (left, right) = (parse(left)?, parse(right)?);
+  Ok(crate::UrlPattern::compare_component(kind, &left, &right) as i8)
+}
+
 pub fn process_match_input<'a>(
   input: StringOrInit<'a>,
   base_url_str: Option<&str>,
@@ -284,58 +334,8 @@ pub fn process_match_input<'a>(
   Ok(Some((init, inputs)))
 }
 
-#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
-pub struct MatchInput {
-  pub protocol: String,
-  pub username: String,
-  pub password: String,
-  pub hostname: String,
-  pub port: String,
-  pub pathname: String,
-  pub search: String,
-  pub hash: String,
-}
-
 pub fn parse_match_input(
   input: crate::UrlPatternMatchInput,
 ) -> Option<MatchInput> {
-  let mut i = MatchInput::default();
-  match input {
-    crate::UrlPatternMatchInput::Init(init) => {
-      if let Ok(apply_result) = init.process(
-        crate::canonicalize_and_process::ProcessType::Url,
-        Some(i.protocol),
-        Some(i.username),
-        Some(i.password),
-        Some(i.hostname),
-        Some(i.port),
-        Some(i.pathname),
-        Some(i.search),
-        Some(i.hash),
-      ) {
-        i.protocol = apply_result.protocol.unwrap();
-        i.username = apply_result.username.unwrap();
-        i.password = apply_result.password.unwrap();
-        i.hostname = apply_result.hostname.unwrap();
-        i.port = apply_result.port.unwrap();
-        i.pathname = apply_result.pathname.unwrap();
-        i.search = apply_result.search.unwrap();
-        i.hash = apply_result.hash.unwrap();
-      } else {
-        return None;
-      }
-    }
-    crate::UrlPatternMatchInput::Url(url) => {
-      i.protocol = url.scheme().to_string();
-      i.username = url.username().to_string();
-      i.password = url.password().unwrap_or_default().to_string();
-      i.hostname = url.host_str().unwrap_or_default().to_string();
-      i.port = url::quirks::port(&url).to_string();
-      i.pathname = url::quirks::pathname(&url).to_string();
-      i.search = url.query().unwrap_or_default().to_string();
-      i.hash = url.fragment().unwrap_or_default().to_string();
-    }
-  }
-
-  Some(i)
+  MatchInput::parse(input)
 }
@@ -1,12 +1,42 @@
 use crate::parser::RegexSyntax;
 
+/// The flags a regular expression is compiled with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RegExpFlags {
+  /// Letters match regardless of their case (the `i` flag).
+  pub ignore_case: bool,
+  /// The pattern and the text are handled as unicode code points (the `u`
+  /// flag). This is always set for the regular expressions of a pattern.
+  pub unicode: bool,
+}
+
+impl std::fmt::Display for RegExpFlags {
+  /// Format the flags as an ECMAScript flags string, like `ui`.
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.unicode {
+      f.write_str("u")?;
+    }
+    if self.ignore_case {
+      f.write_str("i")?;
+    }
+    Ok(())
+  }
+}
+
 pub trait RegExp: Sized {
+  /// The error returned when a pattern is invalid. Its message is included in
+  /// the [crate::Error::RegExp] error of the URL pattern.
+  type Error: std::fmt::Display;
+
   fn syntax() -> RegexSyntax;
 
-  /// Generates a regexp pattern for the given string. If the pattern is
-  /// invalid, the parse function should return an error.
-  #[allow(clippy::result_unit_err)]
-  fn parse(pattern: &str, flags: &str, force_eval: bool) -> Result<Self, ()>;
+  /// Generates a regexp pattern for the given string and flags. If the
+  /// pattern is invalid, the parse function should return an error.
+  fn parse(
+    pattern: &str,
+    flags: RegExpFlags,
+    force_eval: bool,
+  ) -> Result<Self, Self::Error>;
 
   /// Matches the given text against the regular expression and returns the list
   /// of captures. The matches are returned in the order they appear in the
@@ -21,12 +51,21 @@ pub trait RegExp: Sized {
 }
 
 impl RegExp for regex::Regex {
+  type Error = regex::Error;
+
   fn syntax() -> RegexSyntax {
     RegexSyntax::Rust
   }
 
-  fn parse(pattern: &str, _flags: &str, _force_eval: bool) -> Result<Self, ()> {
-    regex::Regex::new(pattern).map_err(|_| ())
+  fn parse(
+    pattern: &str,
+    flags: RegExpFlags,
+    _force_eval: bool,
+  ) -> Result<Self, Self::Error> {
+    regex::RegexBuilder::new(pattern)
+      .case_insensitive(flags.ignore_case)
+      .unicode(flags.unicode)
+      .build()
   }
 
   fn matches<'a>(&self, text: &'a str) -> Option<Vec<Option<&'a str>>> {
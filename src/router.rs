@@ -0,0 +1,259 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::Error;
+use crate::MatchInput;
+use crate::UrlPattern;
+use crate::UrlPatternMatchInput;
+use crate::UrlPatternResult;
+use crate::component;
+use crate::component::Component;
+use crate::matcher::InnerMatcher;
+use crate::parser::PartModifier;
+use crate::parser::PartType;
+use crate::regexp::RegExp;
+
+/// A router that maps [UrlPattern]s to values, and looks up the value of the
+/// best matching pattern for an input.
+///
+/// If more than one pattern matches, the most specific one wins. Patterns are
+/// compared component by component, in the order the components appear in a
+/// URL, with [UrlPattern::compare_component]: fixed text is preferred over a
+/// regexp group, over a `:name` segment wildcard, over a `*` full wildcard.
+/// Of equally specific patterns, the one that was inserted first wins.
+///
+/// Patterns are indexed by their protocol and hostname if these are fixed
+/// text, and by the fixed segments at the start of their pathname, so only
+/// the patterns that can possibly match are executed for an input.
+///
+/// # Examples
+///
+/// ```
+/// use urlpattern::Router;
+/// use urlpattern::UrlPattern;
+/// use urlpattern::UrlPatternMatchInput;
+///
+///# fn main() {
+/// let parse = |pattern| {
+///   <UrlPattern>::parse_str(pattern, None, Default::default()).unwrap()
+/// };
+/// let mut router = Router::new();
+/// router.insert(parse("https://example.com/*"), "fallback");
+/// router.insert(parse("https://example.com/users/:id"), "user");
+/// router.insert(parse("https://example.com/users/new"), "new user");
+///
+/// let url = "https://example.com/users/new".parse().unwrap();
+/// let (value, _) = router.lookup(UrlPatternMatchInput::Url(url)).unwrap().unwrap();
+/// assert_eq!(*value, "new user");
+///
+/// let url = "https://example.com/users/7".parse().unwrap();
+/// let (value, result) = router.lookup(UrlPatternMatchInput::Url(url)).unwrap().unwrap();
+/// assert_eq!(*value, "user");
+/// assert_eq!(result.pathname.groups["id"].as_deref(), Some("7"));
+///# }
+/// ```
+#[derive(Debug)]
+pub struct Router<T, R: RegExp = regex::Regex> {
+  routes: Vec<(UrlPattern<R>, T)>,
+  /// The patterns by their protocol and hostname, if these are fixed text.
+  index: HashMap<(Option<String>, Option<String>), PathnameIndex>,
+}
+
+/// The patterns with the same protocol and hostname key, by the fixed
+/// segments at the start of their pathname.
+#[derive(Debug, Default)]
+struct PathnameIndex {
+  /// Patterns that match the pathname case-sensitively.
+  exact: SegmentTrie,
+  /// Patterns that ignore case, keyed by their lowercased segments.
+  folded: SegmentTrie,
+}
+
+#[derive(Debug, Default)]
+struct SegmentTrie {
+  /// The patterns whose fixed segments end at this node.
+  routes: Vec<usize>,
+  children: HashMap<String, SegmentTrie>,
+}
+
+impl SegmentTrie {
+  fn insert<'a>(
+    &mut self,
+    mut segments: impl Iterator<Item = &'a str>,
+    route: usize,
+  ) {
+    match segments.next() {
+      Some(segment) => self
+        .children
+        .entry(segment.to_owned())
+        .or_default()
+        .insert(segments, route),
+      None => self.routes.push(route),
+    }
+  }
+
+  /// Collect the patterns of all nodes on the path of the given segments.
+  fn collect<'a>(
+    &self,
+    mut segments: impl Iterator<Item = &'a str>,
+    routes: &mut Vec<usize>,
+  ) {
+    routes.extend(&self.routes);
+    if let Some(child) = segments.next().and_then(|s| self.children.get(s)) {
+      child.collect(segments, routes);
+    }
+  }
+}
+
+impl<T, R: RegExp> Default for Router<T, R> {
+  fn default() -> Self {
+    Router {
+      routes: vec![],
+      index: HashMap::new(),
+    }
+  }
+}
+
+impl<T, R: RegExp> Router<T, R> {
+  /// Create an empty router.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Add a pattern with its value to the router.
+  pub fn insert(&mut self, pattern: UrlPattern<R>, value: T) {
+    let route = self.routes.len();
+    let key = (literal(&pattern.protocol), literal(&pattern.hostname));
+    let index = self.index.entry(key).or_default();
+    let prefix = &fixed_prefix(&pattern.pathname);
+    if pattern.pathname.matcher.ignore_case {
+      // Note: the key stops at the first non-ASCII char, so that it only
+      // has to be compared with the lowercased input.
+      let end = prefix.find(|c: char| !c.is_ascii()).unwrap_or(prefix.len());
+      let prefix = prefix[..end].to_ascii_lowercase();
+      index.folded.insert(fixed_segments(&prefix), route);
+    } else {
+      index.exact.insert(fixed_segments(prefix), route);
+    }
+    self.routes.push((pattern, value));
+  }
+
+  /// The patterns and their values, in the order they were inserted.
+  pub fn routes(&self) -> &[(UrlPattern<R>, T)] {
+    &self.routes
+  }
+
+  /// The number of patterns in this router.
+  pub fn len(&self) -> usize {
+    self.routes.len()
+  }
+
+  /// Returns whether this router contains no patterns.
+  pub fn is_empty(&self) -> bool {
+    self.routes.is_empty()
+  }
+
+  /// Look up the best matching pattern for a [UrlPatternMatchInput],
+  /// returning its value and the [UrlPatternResult] of the match.
+  pub fn lookup(
+    &self,
+    input: UrlPatternMatchInput,
+  ) -> Result<Option<(&T, UrlPatternResult)>, Error> {
+    let inputs = input.inputs();
+    let input = match MatchInput::parse(input) {
+      Some(input) => input,
+      None => return Ok(None),
+    };
+    let mut candidates = self.candidates(&input);
+    candidates.sort_by(|&a, &b| self.compare(a, b));
+    for route in candidates {
+      let (pattern, value) = &self.routes[route];
+      if let Some(result) = pattern.exec_borrowed(&input) {
+        return Ok(Some((value, result.into_owned(inputs))));
+      }
+    }
+    Ok(None)
+  }
+
+  /// The patterns that can match the input according to the index.
+  pub(crate) fn candidates(&self, input: &MatchInput) -> Vec<usize> {
+    // Note: unicode case folding also matches `s` with the long s, which is
+    // already lowercase. The Kelvin sign is lowercased to `k`.
+    let folded_pathname = input.pathname.to_lowercase().replace('ſ', "s");
+    let mut candidates = vec![];
+    for protocol in [Some(&input.protocol), None] {
+      for hostname in [Some(&input.hostname), None] {
+        let key = (protocol.cloned(), hostname.cloned());
+        if let Some(index) = self.index.get(&key) {
+          let segments = fixed_segments(&input.pathname);
+          index.exact.collect(segments, &mut candidates);
+          let segments = fixed_segments(&folded_pathname);
+          index.folded.collect(segments, &mut candidates);
+        }
+      }
+    }
+    candidates
+  }
+
+  /// Order two patterns so that the best match comes first.
+  fn compare(&self, left: usize, right: usize) -> Ordering {
+    let left_components = self.routes[left].0.components();
+    let right_components = self.routes[right].0.components();
+    left_components
+      .into_iter()
+      .zip(right_components)
+      .map(|(left, right)| {
+        component::compare_part_lists(&right.part_list, &left.part_list)
+      })
+      .find(|ordering| ordering.is_ne())
+      .unwrap_or_else(|| left.cmp(&right))
+  }
+}
+
+impl<T, R: RegExp> FromIterator<(UrlPattern<R>, T)> for Router<T, R> {
+  fn from_iter<I: IntoIterator<Item = (UrlPattern<R>, T)>>(iter: I) -> Self {
+    let mut router = Router::new();
+    for (pattern, value) in iter {
+      router.insert(pattern, value);
+    }
+    router
+  }
+}
+
+/// The text a component matches, if it only matches fixed text.
+fn literal<R: RegExp>(component: &Component<R>) -> Option<String> {
+  match &component.matcher.inner {
+    InnerMatcher::Literal { literal } if !component.matcher.ignore_case => {
+      Some(literal.clone())
+    }
+    _ => None,
+  }
+}
+
+/// The fixed text every input a component matches starts with: the leading
+/// fixed text of the component, and the prefix of the first group if that
+/// group is not optional or repeated.
+fn fixed_prefix<R: RegExp>(component: &Component<R>) -> String {
+  let mut prefix = String::new();
+  for part in &component.part_list {
+    if part.modifier != PartModifier::None {
+      break;
+    }
+    if part.kind != PartType::FixedText {
+      prefix.push_str(&part.prefix);
+      break;
+    }
+    prefix.push_str(&part.value);
+  }
+  prefix
+}
+
+/// The segments of a pathname that are followed by a `/`. Any pathname that
+/// starts with a prefix also starts with the fixed segments of that prefix.
+fn fixed_segments(pathname: &str) -> impl Iterator<Item = &str> {
+  let mut segments: Vec<_> = pathname.split('/').collect();
+  segments.pop();
+  segments.into_iter()
+}
@@ -0,0 +1,343 @@
+// Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
+
+use std::collections::HashMap;
+
+use regex::RegexSet;
+use regex::RegexSetBuilder;
+
+use crate::MatchInput;
+use crate::UrlPattern;
+use crate::UrlPatternMatchInput;
+use crate::UrlPatternResult;
+use crate::component::Component;
+use crate::matcher::Captures;
+use crate::matcher::InnerMatcher;
+use crate::parser::RegexSyntax;
+use crate::regexp::RegExp;
+
+/// The order in which components are matched. The most selective components
+/// come first, so that most patterns are discarded before the remaining
+/// components have to be matched at all.
+const MATCH_ORDER: [usize; 8] = [5, 3, 0, 4, 6, 7, 1, 2];
+
+/// The exec results of every unique matcher of every component. A matcher
+/// that was never run is `None`. Matchers that are decided by the prefilter
+/// of their component are only run to get the captures of a match.
+type ExecResults<'a> = Vec<Vec<Option<Option<Captures<'a>>>>>;
+
+/// A set of [UrlPattern]s that can be matched against an input in a single
+/// pass.
+///
+/// The input is only parsed once for the whole set, and matchers that are
+/// shared between patterns (for example the `*` matcher of the protocol
+/// component of most patterns) are only run once per input. Components that
+/// only match fixed text are looked up in a hash map, and with the default
+/// [regex::Regex] engine, the regular expressions of the other components are
+/// matched in a single pass with a [regex::RegexSet]. Only the patterns that
+/// match are then executed to get their captures.
+///
+/// # Examples
+///
+/// ```
+/// use urlpattern::UrlPattern;
+/// use urlpattern::UrlPatternInit;
+/// use urlpattern::UrlPatternMatchInput;
+/// use urlpattern::UrlPatternSet;
+///
+///# fn main() {
+/// let set: UrlPatternSet = ["/users/:id", "/users/new", "/posts/*"]
+///   .into_iter()
+///   .map(|pathname| {
+///     let init = UrlPatternInit {
+///       pathname: Some(pathname.to_owned()),
+///       ..Default::default()
+///     };
+///     <UrlPattern>::parse(init, Default::default()).unwrap()
+///   })
+///   .collect();
+///
+/// let url = "https://example.com/users/new".parse().unwrap();
+/// let matches = set.matches(UrlPatternMatchInput::Url(url));
+/// assert_eq!(matches, vec![0, 1]);
+///# }
+/// ```
+#[derive(Debug)]
+pub struct UrlPatternSet<R: RegExp = regex::Regex> {
+  patterns: Vec<UrlPattern<R>>,
+  pub(crate) components: [ComponentSet; 8],
+}
+
+/// The deduplicated matchers of a single component across all patterns.
+#[derive(Debug, Default)]
+pub(crate) struct ComponentSet {
+  /// For every unique matcher, the index of the first pattern that uses it.
+  unique: Vec<usize>,
+  /// For every pattern, the index of its matcher in `unique`.
+  lookup: Vec<usize>,
+  /// The unique matchers that only match fixed text, by that text.
+  pub(crate) literals: HashMap<String, Vec<usize>>,
+  /// The regular expressions of the other unique matchers, and the index in
+  /// `unique` of every regular expression in the set.
+  pub(crate) regex_set: Option<(RegexSet, Vec<usize>)>,
+  /// For every unique matcher, whether `literals` or `regex_set` decide if it
+  /// matches, so that it does not have to be run to find out.
+  prefiltered: Vec<bool>,
+}
+
+impl ComponentSet {
+  /// Build the prefilter of the unique matchers of a component.
+  fn build_prefilter<R: RegExp>(&mut self, components: &[&Component<R>]) {
+    self.prefiltered = vec![false; self.unique.len()];
+    let mut patterns = vec![];
+    let mut indices = vec![];
+    for (unique, component) in components.iter().enumerate() {
+      let matcher = &component.matcher;
+      match &matcher.inner {
+        InnerMatcher::Literal { literal } if !matcher.ignore_case => {
+          let literal =
+            format!("{}{literal}{}", matcher.prefix, matcher.suffix);
+          self.literals.entry(literal).or_default().push(unique);
+          self.prefiltered[unique] = true;
+        }
+        // Note: the regular expressions of the other engines may not be
+        // valid for a regex::RegexSet. Case insensitive matchers are always
+        // run, because a regex::RegexSet does not fold case the same way.
+        _ if R::syntax() == RegexSyntax::Rust && !matcher.ignore_case => {
+          if let Ok(regexp) = &component.regexp {
+            patterns.push(regexp.pattern_string().to_owned());
+            indices.push(unique);
+          }
+        }
+        _ => {}
+      }
+    }
+    // A set of a single regular expression is not faster than its matcher.
+    if indices.len() < 2 {
+      return;
+    }
+    // Note: if the set is too large to compile, the matchers are run one by
+    // one instead.
+    if let Ok(regex_set) = RegexSetBuilder::new(patterns).build() {
+      for &unique in &indices {
+        self.prefiltered[unique] = true;
+      }
+      self.regex_set = Some((regex_set, indices));
+    }
+  }
+
+  /// For every unique matcher, whether it matches the input. Matchers that
+  /// are not prefiltered are reported as not matching.
+  fn prefilter(&self, input: &str) -> Vec<bool> {
+    let mut matched = vec![false; self.unique.len()];
+    for &unique in self.literals.get(input).into_iter().flatten() {
+      matched[unique] = true;
+    }
+    if let Some((regex_set, indices)) = &self.regex_set {
+      for i in regex_set.matches(input).iter() {
+        matched[indices[i]] = true;
+      }
+    }
+    matched
+  }
+}
+
+impl<R: RegExp> UrlPatternSet<R> {
+  /// Create a new set from a list of compiled patterns. The indices returned
+  /// when matching are indices into this list.
+  pub fn new(patterns: Vec<UrlPattern<R>>) -> Self {
+    let components = std::array::from_fn(|i| {
+      let mut set = ComponentSet::default();
+      let mut seen = HashMap::new();
+      for (index, pattern) in patterns.iter().enumerate() {
+        let component = pattern.components()[i];
+        let unique = match matcher_key(component) {
+          Some(key) => *seen.entry(key).or_insert_with(|| {
+            set.unique.push(index);
+            set.unique.len() - 1
+          }),
+          None => {
+            set.unique.push(index);
+            set.unique.len() - 1
+          }
+        };
+        set.lookup.push(unique);
+      }
+      let components: Vec<_> = set
+        .unique
+        .iter()
+        .map(|&index| patterns[index].components()[i])
+        .collect();
+      set.build_prefilter(&components);
+      set
+    });
+    UrlPatternSet {
+      patterns,
+      components,
+    }
+  }
+
+  /// The patterns in this set.
+  pub fn patterns(&self) -> &[UrlPattern<R>] {
+    &self.patterns
+  }
+
+  /// The number of patterns in this set.
+  pub fn len(&self) -> usize {
+    self.patterns.len()
+  }
+
+  /// Returns whether this set contains no patterns.
+  pub fn is_empty(&self) -> bool {
+    self.patterns.is_empty()
+  }
+
+  /// Match a [UrlPatternMatchInput] against all patterns in the set, returning
+  /// the indices of all patterns that match in ascending order. An input that
+  /// can not be parsed does not match any pattern.
+  pub fn matches(&self, input: UrlPatternMatchInput) -> Vec<usize> {
+    let input = match MatchInput::parse(input) {
+      Some(input) => input,
+      None => return vec![],
+    };
+    let (alive, _) = self.match_components(&input);
+    alive_indices(&alive).collect()
+  }
+
+  /// Execute all patterns in the set against a [UrlPatternMatchInput],
+  /// returning the index and [UrlPatternResult] of every pattern that matches,
+  /// in ascending order of their index. An input that can not be parsed does
+  /// not match any pattern.
+  pub fn exec(
+    &self,
+    input: UrlPatternMatchInput,
+  ) -> Vec<(usize, UrlPatternResult)> {
+    let inputs = input.inputs();
+    let input = match MatchInput::parse(input) {
+      Some(input) => input,
+      None => return vec![],
+    };
+    let (alive, mut results) = self.match_components(&input);
+    let component_inputs = match_inputs(&input);
+    alive_indices(&alive)
+      .map(|index| {
+        let pattern = self.patterns[index].components();
+        let [
+          protocol,
+          username,
+          password,
+          hostname,
+          port,
+          pathname,
+          search,
+          hash,
+        ] = std::array::from_fn(|i| {
+          let unique = self.components[i].lookup[index];
+          // Note: unwrap is safe, because every component of a pattern that
+          // is still alive matches.
+          let exec_result = results[i][unique]
+            .get_or_insert_with(|| {
+              pattern[i].matcher.matches(component_inputs[i])
+            })
+            .clone()
+            .unwrap();
+          pattern[i]
+            .create_match_result(component_inputs[i], exec_result)
+            .into_owned()
+        });
+        let result = UrlPatternResult {
+          inputs: inputs.clone(),
+          protocol,
+          username,
+          password,
+          hostname,
+          port,
+          pathname,
+          search,
+          hash,
+        };
+        (index, result)
+      })
+      .collect()
+  }
+
+  /// Match every component of the input against the deduplicated matchers of
+  /// all patterns that have not yet been ruled out. Returns which patterns
+  /// matched, and the memoized exec results of every matcher that was run.
+  fn match_components<'a>(
+    &self,
+    input: &'a MatchInput,
+  ) -> (Vec<bool>, ExecResults<'a>) {
+    let inputs = match_inputs(input);
+    let mut alive = vec![true; self.patterns.len()];
+    let mut results: ExecResults<'a> = self
+      .components
+      .iter()
+      .map(|set| vec![None; set.unique.len()])
+      .collect();
+    for i in MATCH_ORDER {
+      if !alive.contains(&true) {
+        break;
+      }
+      let set = &self.components[i];
+      let prefiltered = set.prefilter(inputs[i]);
+      for (index, alive) in alive.iter_mut().enumerate() {
+        if !*alive {
+          continue;
+        }
+        let unique = set.lookup[index];
+        let matched = if set.prefiltered[unique] {
+          prefiltered[unique]
+        } else {
+          results[i][unique]
+            .get_or_insert_with(|| {
+              let component = self.patterns[set.unique[unique]].components()[i];
+              component.matcher.matches(inputs[i])
+            })
+            .is_some()
+        };
+        if !matched {
+          *alive = false;
+        }
+      }
+    }
+    (alive, results)
+  }
+}
+
+impl<R: RegExp> FromIterator<UrlPattern<R>> for UrlPatternSet<R> {
+  fn from_iter<T: IntoIterator<Item = UrlPattern<R>>>(iter: T) -> Self {
+    Self::new(iter.into_iter().collect())
+  }
+}
+
+fn match_inputs(input: &MatchInput) -> [&str; 8] {
+  [
+    &input.protocol,
+    &input.username,
+    &input.password,
+    &input.hostname,
+    &input.port,
+    &input.pathname,
+    &input.search,
+    &input.hash,
+  ]
+}
+
+/// Two components with the same regular expression and case sensitivity
+/// always produce the same exec result, so their matchers only need to be run
+/// once. Components with an invalid regular expression are never shared.
+fn matcher_key<R: RegExp>(component: &Component<R>) -> Option<(String, bool)> {
+  let regexp = component.regexp.as_ref().ok()?;
+  Some((
+    regexp.pattern_string().to_owned(),
+    component.matcher.ignore_case,
+  ))
+}
+
+fn alive_indices(alive: &[bool]) -> impl Iterator<Item = usize> + '_ {
+  alive
+    .iter()
+    .enumerate()
+    .filter(|(_, alive)| **alive)
+    .map(|(index, _)| index)
+}
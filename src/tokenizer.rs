@@ -1,6 +1,8 @@
 // Copyright 2018-2021 the Deno authors. All rights reserved. MIT license.
 
 use crate::Error;
+use crate::error::Diagnostic;
+use crate::error::ErrorLocation;
 use crate::error::TokenizerError;
 use icu_properties::{
   CodePointSetDataBorrowed,
@@ -31,6 +33,10 @@ pub struct Token<'a> {
   pub kind: TokenType,
   pub index: usize,
   pub value: &'a str,
+  /// The index after the last code point of the token in the input. Together
+  /// with `index`, this is the span of the whole token, including syntax like
+  /// the `:` of a name or the parentheses of a regexp.
+  pub end: usize,
 }
 
 // Ref: https://wicg.github.io/urlpattern/#tokenize-policy
@@ -48,6 +54,8 @@ struct Tokenizer<'a> {
   index: usize,
   next_index: usize,
   code_point: Option<char>, // TODO: get rid of Option
+  /// The errors and warnings found so far, if they are being collected.
+  diagnostics: Option<Vec<Diagnostic>>,
 }
 
 impl<'a> Tokenizer<'a> {
@@ -91,6 +99,7 @@ impl<'a> Tokenizer<'a> {
       kind,
       index: self.index,
       value,
+      end: next_pos,
     });
     self.index = next_pos;
   }
@@ -102,9 +111,14 @@ impl<'a> Tokenizer<'a> {
     value_pos: usize,
     error: TokenizerError,
   ) -> Result<(), Error> {
+    let error =
+      Error::Tokenizer(error, ErrorLocation::new(value_pos..self.next_index));
     if self.policy == TokenizePolicy::Strict {
-      Err(Error::Tokenizer(error, value_pos))
+      Err(error)
     } else {
+      if let Some(diagnostics) = &mut self.diagnostics {
+        diagnostics.push(error.into());
+      }
       self.add_token_with_default_len(
         TokenType::InvalidChar,
         next_pos,
@@ -114,6 +128,13 @@ impl<'a> Tokenizer<'a> {
     }
   }
 
+  fn add_warning(&mut self, code: &'static str, message: String) {
+    if let Some(diagnostics) = &mut self.diagnostics {
+      let span = self.index..self.next_index;
+      diagnostics.push(Diagnostic::warning(code, message, span));
+    }
+  }
+
   // Ref: https://wicg.github.io/urlpattern/#seek-and-get-the-next-code-point
   #[inline]
   fn seek_and_get_next_codepoint(&mut self, index: usize) {
@@ -127,6 +148,25 @@ pub fn tokenize(
   input: &str,
   policy: TokenizePolicy,
 ) -> Result<Vec<Token>, Error> {
+  tokenize_internal(input, policy, None).map(|(token_list, _)| token_list)
+}
+
+/// Tokenize with the lenient policy, returning the tokens together with every
+/// tokenizing error and warning found in the input.
+pub(crate) fn tokenize_with_diagnostics(
+  input: &str,
+) -> (Vec<Token>, Vec<Diagnostic>) {
+  // Note: unwrap is safe, because the lenient policy never returns an error.
+  let (token_list, diagnostics) =
+    tokenize_internal(input, TokenizePolicy::Lenient, Some(vec![])).unwrap();
+  (token_list, diagnostics.unwrap_or_default())
+}
+
+fn tokenize_internal(
+  input: &str,
+  policy: TokenizePolicy,
+  diagnostics: Option<Vec<Diagnostic>>,
+) -> Result<(Vec<Token>, Option<Vec<Diagnostic>>), Error> {
   let mut tokenizer = Tokenizer {
     input,
     policy,
@@ -134,6 +174,7 @@ pub fn tokenize(
     index: 0,
     next_index: 0,
     code_point: None,
+    diagnostics,
   };
 
   while tokenizer.index < tokenizer.input.len() {
@@ -158,6 +199,13 @@ pub fn tokenize(
       }
       let escaped_index = tokenizer.next_index;
       tokenizer.get_next_codepoint();
+      let escaped = tokenizer.code_point.unwrap();
+      if escaped.is_ascii_alphanumeric() {
+        tokenizer.add_warning(
+          "redundant-escape",
+          format!("`\\{escaped}` matches a literal `{escaped}`"),
+        );
+      }
       tokenizer.add_token_with_default_len(
         TokenType::EscapedChar,
         tokenizer.next_index,
@@ -170,6 +218,10 @@ pub fn tokenize(
       || tokenizer.code_point == Some('\t')
     {
       // ignore newline, carriage return and tab
+      tokenizer.add_warning(
+        "ignored-whitespace",
+        format!("{:?} is ignored", tokenizer.code_point.unwrap()),
+      );
       tokenizer.index = tokenizer.next_index;
       continue;
     }
@@ -324,7 +376,7 @@ pub fn tokenize(
     tokenizer.index,
     tokenizer.index,
   );
-  Ok(tokenizer.token_list)
+  Ok((tokenizer.token_list, tokenizer.diagnostics))
 }
 
 static ID_START: CodePointSetDataBorrowed<'_> =